@@ -14,7 +14,7 @@ fn main() {
     let mut audio_out_l_port = client.register_port("out_l", out_spec).unwrap();
     let mut audio_out_r_port = client.register_port("out_r", out_spec).unwrap();
 
-    let mut freeverb: Freeverb<f32> = Freeverb::new();
+    let mut freeverb = Freeverb::new();
 
     let process = jack::ClosureProcessHandler::new(
         move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
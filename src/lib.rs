@@ -2,6 +2,7 @@
 #![feature(const_generics)]
 #![no_std]
 
+mod math;
 pub mod modules;
 pub mod node;
 
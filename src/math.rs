@@ -0,0 +1,158 @@
+// Floating point helpers that can be evaluated in `const` contexts, where
+// `libm` can't be used. These favour accuracy over speed and are computed in
+// f64 so the results round to the same f32 as their `libm` counterparts in
+// almost all cases.
+
+use core::f64::consts::{FRAC_PI_2, LN_2, PI};
+
+pub(crate) const fn abs(x: f64) -> f64 {
+    if x < 0. {
+        -x
+    } else {
+        x
+    }
+}
+
+// Reduces `x` into [-pi, pi].
+const fn reduce(x: f64) -> f64 {
+    let turns = (x / (2. * PI)) as i64 as f64;
+    let mut x = x - turns * 2. * PI;
+    if x > PI {
+        x -= 2. * PI;
+    } else if x < -PI {
+        x += 2. * PI;
+    }
+    x
+}
+
+pub(crate) const fn sin(x: f64) -> f64 {
+    let mut x = reduce(x);
+
+    // Fold into [-pi/2, pi/2] where the series converges quickly.
+    if x > FRAC_PI_2 {
+        x = PI - x;
+    } else if x < -FRAC_PI_2 {
+        x = -PI - x;
+    }
+
+    let x2 = x * x;
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 1;
+    while n < 14 {
+        term *= -x2 / ((2 * n) as f64 * (2 * n + 1) as f64);
+        sum += term;
+        n += 1;
+    }
+    sum
+}
+
+pub(crate) const fn cos(x: f64) -> f64 {
+    sin(x + FRAC_PI_2)
+}
+
+pub(crate) const fn tan(x: f64) -> f64 {
+    sin(x) / cos(x)
+}
+
+pub(crate) const fn exp(x: f64) -> f64 {
+    // Beyond these the result rounds to zero or overflows
+    if x < -745.2 {
+        return 0.;
+    }
+    if x > 709.8 {
+        return f64::INFINITY;
+    }
+
+    // e^x = 2^n * e^r with |r| <= ln(2) / 2
+    let n = (x / LN_2 + if x < 0. { -0.5 } else { 0.5 }) as i64;
+    let r = x - n as f64 * LN_2;
+
+    let mut term = 1.;
+    let mut sum = 1.;
+    let mut i = 1;
+    while i < 20 {
+        term *= r / i as f64;
+        sum += term;
+        i += 1;
+    }
+
+    // In two halves, so each stays a normal number when 2^n isn't
+    sum * pow2(n / 2) * pow2(n - n / 2)
+}
+
+// 2^n built from its exponent bits, for n from -1022 to 1023.
+const fn pow2(n: i64) -> f64 {
+    f64::from_bits(((n + 1023) as u64) << 52)
+}
+
+pub(crate) const fn sqrt(x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+
+    let mut guess = if x > 1. { x } else { 1. };
+    let mut i = 0;
+    while i < 128 {
+        let next = 0.5 * (guess + x / guess);
+        if next >= guess {
+            break;
+        }
+        guess = next;
+        i += 1;
+    }
+    guess
+}
+
+// The zeroth order modified Bessel function of the first kind.
+pub(crate) const fn bessel_i0(x: f64) -> f64 {
+    let half = x / 2.;
+
+    let mut term = 1.;
+    let mut sum = 1.;
+    let mut k = 1;
+    while k < 500 {
+        let factor = half / k as f64;
+        term *= factor * factor;
+        sum += term;
+        if term < sum * 1e-17 {
+            break;
+        }
+        k += 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_libm() {
+        for i in -100..100 {
+            let x = i as f64 * 0.173;
+            assert!(abs(sin(x) - libm::sin(x)) < 1e-12);
+            assert!(abs(cos(x) - libm::cos(x)) < 1e-12);
+            assert!(abs(exp(x / 10.) - libm::exp(x / 10.)) < 1e-12 * libm::exp(x / 10.));
+            assert!(abs(sqrt(abs(x)) - libm::sqrt(abs(x))) < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_exp_extremes() {
+        for &x in [-745., -720., -700., 700., 709.5].iter() {
+            assert!(abs(exp(x) - libm::exp(x)) <= 1e-12 * libm::exp(x), "{}", x);
+        }
+
+        assert_eq!(exp(-746.), 0.);
+        assert_eq!(exp(f64::NEG_INFINITY), 0.);
+        assert_eq!(exp(710.), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_bessel_i0() {
+        assert_eq!(bessel_i0(0.), 1.);
+        assert!(abs(bessel_i0(1.) - 1.2660658777520082) < 1e-12);
+        assert!(abs(bessel_i0(10.) - 2815.716628466254) < 1e-9);
+    }
+}
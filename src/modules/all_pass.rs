@@ -2,27 +2,59 @@ use super::{DelayLine, Node, ReadableNode};
 
 pub struct AllPass<const N: usize> {
     delay_line: DelayLine<N>,
+    gain: Option<f32>,
 }
 
 impl<const N: usize> AllPass<N> {
     pub fn new() -> Self {
+        Self::with_length(N)
+    }
+
+    pub fn with_length(length: usize) -> Self {
         Self {
-            delay_line: DelayLine::new(),
+            delay_line: DelayLine::with_length(length),
+            gain: None,
         }
     }
+
+    /// By default this uses Freeverb's approximation of an allpass, which
+    /// passes the input through inverted and feeds back by 0.5. Setting a gain
+    /// turns it into a true Schroeder allpass with that coefficient, as used
+    /// for diffusers.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = Some(gain);
+    }
+
+    pub fn length(&self) -> usize {
+        self.delay_line.length()
+    }
+
+    /// Read the internal delay line, see `DelayLine::tap`.
+    pub fn tap(&self, delay: usize) -> f32 {
+        self.delay_line.tap(delay)
+    }
 }
 
 impl<const N: usize> Node<f32, f32> for AllPass<N> {
     fn process(&mut self, input: f32) -> f32 {
         let delayed = self.delay_line.read();
 
-        let output = -input + delayed;
+        match self.gain {
+            None => {
+                let output = -input + delayed;
 
-        let feedback: f32 = 0.5;
+                let feedback: f32 = 0.5;
 
-        self.delay_line.process(input + delayed * feedback);
+                self.delay_line.process(input + delayed * feedback);
 
-        output
+                output
+            }
+            Some(gain) => {
+                let buffered = input + delayed * gain;
+                self.delay_line.process(buffered);
+                delayed - buffered * gain
+            }
+        }
     }
 }
 
@@ -41,4 +73,18 @@ mod tests {
         assert_eq!(allpass.process(0.0), 0.0);
         assert_eq!(allpass.process(0.0), 0.25);
     }
+
+    #[test]
+    fn test_true_allpass_preserves_energy() {
+        let mut allpass: AllPass<7> = AllPass::new();
+        allpass.set_gain(0.7);
+
+        let mut energy = 0.;
+        for i in 0..2000 {
+            let output = allpass.process(if i == 0 { 1.0 } else { 0.0 });
+            energy += output * output;
+        }
+
+        assert!((energy - 1.).abs() < 1e-5);
+    }
 }
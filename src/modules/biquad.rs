@@ -1,5 +1,8 @@
 use super::Node;
-use core::f32::consts::PI;
+use crate::math;
+use core::f32::consts::{PI, SQRT_2};
+use core::f64::consts::LN_10;
+use core::fmt;
 use libm;
 
 // Stolen from here:
@@ -23,7 +26,93 @@ pub struct BiquadParameters {
     pub peak_gain: f32,
 }
 
-#[derive(Default)]
+// The range that `BiquadParameters::clamp` keeps parameters within.
+pub const MIN_CUTOFF: f32 = 0.0002;
+pub const MAX_CUTOFF: f32 = 0.499;
+pub const MIN_Q: f32 = 0.001;
+pub const MAX_Q: f32 = 1000.;
+pub const MAX_PEAK_GAIN: f32 = 120.;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BiquadError {
+    /// The cutoff must be between 0 and 0.5 (Nyquist), exclusive.
+    InvalidCutoff,
+    /// The q must be greater than 0.
+    InvalidQ,
+    /// The peak gain must be finite.
+    InvalidPeakGain,
+    /// The resulting filter has poles on or outside the unit circle.
+    Unstable,
+}
+
+impl fmt::Display for BiquadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BiquadError::*;
+
+        let message = match self {
+            InvalidCutoff => "cutoff must be within (0, 0.5)",
+            InvalidQ => "q must be greater than 0",
+            InvalidPeakGain => "peak gain must be finite",
+            Unstable => "filter poles must lie inside the unit circle",
+        };
+
+        f.write_str(message)
+    }
+}
+
+impl BiquadParameters {
+    pub fn validate(&self) -> Result<(), BiquadError> {
+        if !(self.cutoff > 0. && self.cutoff < 0.5) {
+            return Err(BiquadError::InvalidCutoff);
+        }
+
+        if !(self.q > 0. && self.q.is_finite()) {
+            return Err(BiquadError::InvalidQ);
+        }
+
+        if !self.peak_gain.is_finite() {
+            return Err(BiquadError::InvalidPeakGain);
+        }
+
+        Ok(())
+    }
+
+    /// Force the parameters into the ranges above, so that they validate.
+    /// NaNs are replaced with a fully open, flat response. Extreme
+    /// combinations, such as a deep cut at a very low cutoff, can still round
+    /// to an unstable design, so the result should be checked with
+    /// `is_stable`.
+    pub fn clamp(self) -> Self {
+        let clamp = |value: f32, min: f32, max: f32, nan: f32| {
+            if value.is_nan() {
+                nan
+            } else {
+                value.max(min).min(max)
+            }
+        };
+
+        Self {
+            cutoff: clamp(self.cutoff, MIN_CUTOFF, MAX_CUTOFF, MAX_CUTOFF),
+            q: clamp(self.q, MIN_Q, MAX_Q, 0.707),
+            peak_gain: clamp(self.peak_gain, -MAX_PEAK_GAIN, MAX_PEAK_GAIN, 0.),
+        }
+    }
+}
+
+// An s-domain prototype H(s) = (b0 + b1 s + b2 s^2) / (a0 + a1 s + a2 s^2),
+// normalised so that the cutoff sits at 1 rad/s.
+#[derive(Copy, Clone)]
+pub struct AnalogPrototype {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+
+    pub a0: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+#[derive(Default, Copy, Clone)]
 pub struct BiquadCoefficients {
     a0: f32,
     a1: f32,
@@ -34,109 +123,246 @@ pub struct BiquadCoefficients {
 }
 
 impl BiquadCoefficients {
-    fn update(
-        &mut self,
+    /// Design from an analog prototype using the bilinear transform, prewarped
+    /// so that the prototype's cutoff lands exactly on `cutoff` (as a fraction
+    /// of the sample rate).
+    pub fn bilinear(
+        AnalogPrototype {
+            b0,
+            b1,
+            b2,
+            a0,
+            a1,
+            a2,
+        }: AnalogPrototype,
+        cutoff: f32,
+    ) -> Self {
+        let k = libm::tanf(PI * cutoff);
+        let kk = k * k;
+
+        let norm = 1. / (a0 * kk + a1 * k + a2);
+
+        Self {
+            a0: (b0 * kk + b1 * k + b2) * norm,
+            a1: 2. * (b0 * kk - b2) * norm,
+            a2: (b0 * kk - b1 * k + b2) * norm,
+            b1: 2. * (a0 * kk - a2) * norm,
+            b2: (a0 * kk - a1 * k + a2) * norm,
+        }
+    }
+
+    /// Design from an analog prototype by mapping its poles and zeros with
+    /// z = e^(sT). Zeros at infinity are dropped and the gain is matched at DC,
+    /// or at the cutoff when the prototype has no DC response.
+    pub fn matched_z(prototype: AnalogPrototype, cutoff: f32) -> Self {
+        let AnalogPrototype {
+            b0,
+            b1,
+            b2,
+            a0,
+            a1,
+            a2,
+        } = prototype;
+
+        let wc = 2. * PI * cutoff;
+
+        let numerator = map_roots([b0, b1, b2], wc);
+        let denominator = map_roots([a0, a1, a2], wc);
+
+        let gain = if b0 != 0. {
+            (b0 / a0) * denominator.iter().sum::<f32>() / numerator.iter().sum::<f32>()
+        } else {
+            analog_magnitude([b0, b1, b2], 1.) / analog_magnitude([a0, a1, a2], 1.)
+                * digital_magnitude(denominator, wc)
+                / digital_magnitude(numerator, wc)
+        };
+
+        Self {
+            a0: numerator[0] * gain,
+            a1: numerator[1] * gain,
+            a2: numerator[2] * gain,
+            b1: denominator[1],
+            b2: denominator[2],
+        }
+    }
+
+    /// Whether both poles lie strictly inside the unit circle.
+    pub fn is_stable(&self) -> bool {
+        // Stability triangle for z^2 + b1 z + b2
+        self.b2.abs() < 1. && self.b1.abs() < 1. + self.b2
+    }
+
+    fn update(&mut self, biquad_type: BiquadType, parameters: BiquadParameters) {
+        let v = libm::powf(10., libm::fabsf(parameters.peak_gain) / 20.0);
+        let k = libm::tanf(PI * parameters.cutoff);
+
+        *self = Self::from_terms(biquad_type, parameters, k, v, libm::sqrtf(2. * v));
+    }
+
+    /// Same as the runtime design used by `Biquad`, but usable in `const`
+    /// contexts so coefficients for fixed filters can be computed at build
+    /// time.
+    pub const fn design(biquad_type: BiquadType, parameters: BiquadParameters) -> Self {
+        let v = math::exp(math::abs(parameters.peak_gain as f64) / 20.0 * LN_10) as f32;
+        let k = math::tan(PI as f64 * parameters.cutoff as f64) as f32;
+
+        Self::from_terms(
+            biquad_type,
+            parameters,
+            k,
+            v,
+            math::sqrt(2. * v as f64) as f32,
+        )
+    }
+
+    const fn from_terms(
         biquad_type: BiquadType,
-        BiquadParameters {
-            cutoff,
-            q,
-            peak_gain,
-        }: BiquadParameters,
-    ) {
+        BiquadParameters { q, peak_gain, .. }: BiquadParameters,
+        k: f32,
+        v: f32,
+        sqrt_2v: f32,
+    ) -> Self {
         use BiquadType::*;
 
+        let mut c = Self {
+            a0: 0.,
+            a1: 0.,
+            a2: 0.,
+            b1: 0.,
+            b2: 0.,
+        };
+
         let norm: f32;
-        let v = libm::powf(10., libm::fabsf(peak_gain) / 20.0);
-        let k = libm::tanf(PI * cutoff);
 
         match biquad_type {
             Lowpass => {
                 norm = 1. / (1. + k / q + k * k);
-                self.a0 = k * k * norm;
-                self.a1 = 2. * self.a0;
-                self.a2 = self.a0;
-                self.b1 = 2. * (k * k - 1.) * norm;
-                self.b2 = 1. - k / q + k * k;
+                c.a0 = k * k * norm;
+                c.a1 = 2. * c.a0;
+                c.a2 = c.a0;
+                c.b1 = 2. * (k * k - 1.) * norm;
+                c.b2 = (1. - k / q + k * k) * norm;
             }
             Highpass => {
                 norm = 1. / (1. + k / q + k * k);
-                self.a0 = 1. * norm;
-                self.a1 = -2. * self.a0;
-                self.a2 = self.a0;
-                self.b1 = 2. * (k * k - 1.) * norm;
-                self.b2 = (1. - k / q + k * k) * norm;
+                c.a0 = 1. * norm;
+                c.a1 = -2. * c.a0;
+                c.a2 = c.a0;
+                c.b1 = 2. * (k * k - 1.) * norm;
+                c.b2 = (1. - k / q + k * k) * norm;
             }
             Bandpass => {
                 norm = 1. / (1. + k / q + k * k);
-                self.a0 = k / q * norm;
-                self.a1 = 0.;
-                self.a2 = -self.a0;
-                self.b1 = 2. * (k * k - 1.) * norm;
-                self.b2 = (1. - k / q + k * k) * norm;
+                c.a0 = k / q * norm;
+                c.a1 = 0.;
+                c.a2 = -c.a0;
+                c.b1 = 2. * (k * k - 1.) * norm;
+                c.b2 = (1. - k / q + k * k) * norm;
             }
             Notch => {
                 norm = 1. / (1. + k / q + k * k);
-                self.a0 = (1. + k * k) * norm;
-                self.a1 = 2. * (k * k - 1.) * norm;
-                self.a2 = self.a0;
-                self.b1 = self.a1;
-                self.b2 = (1. - k / q + k * k) * norm;
+                c.a0 = (1. + k * k) * norm;
+                c.a1 = 2. * (k * k - 1.) * norm;
+                c.a2 = c.a0;
+                c.b1 = c.a1;
+                c.b2 = (1. - k / q + k * k) * norm;
             }
             Peak => {
                 if peak_gain >= 0. {
                     norm = 1. / (1. + 1. / q * k + k * k);
-                    self.a1 = 2. * (k * k - 1.) * norm;
-                    self.a0 = (1. + v / q * k + k * k) * norm;
-                    self.a2 = (1. - v / q * k + k * k) * norm;
-                    self.b1 = self.a1;
-                    self.b2 = (1. - 1. / q * k + k * k) * norm;
+                    c.a1 = 2. * (k * k - 1.) * norm;
+                    c.a0 = (1. + v / q * k + k * k) * norm;
+                    c.a2 = (1. - v / q * k + k * k) * norm;
+                    c.b1 = c.a1;
+                    c.b2 = (1. - 1. / q * k + k * k) * norm;
                 } else {
                     norm = 1. / (1. + v / q * k + k * k);
-                    self.a1 = 2. * (k * k - 1.) * norm;
-                    self.a0 = (1. + 1. / q * k + k * k) * norm;
-                    self.a2 = (1. - 1. / q * k + k * k) * norm;
-                    self.b1 = self.a1;
-                    self.b2 = (1. - v / q * k + k * k) * norm;
+                    c.a1 = 2. * (k * k - 1.) * norm;
+                    c.a0 = (1. + 1. / q * k + k * k) * norm;
+                    c.a2 = (1. - 1. / q * k + k * k) * norm;
+                    c.b1 = c.a1;
+                    c.b2 = (1. - v / q * k + k * k) * norm;
                 }
             }
             Lowshelf => {
                 if peak_gain >= 0. {
-                    norm = 1. / (1. + libm::sqrtf(2.) * k + k * k);
-                    self.a0 = (1. + libm::sqrtf(2. * v) * k + v * k * k) * norm;
-                    self.a1 = 2. * (v * k * k - 1.) * norm;
-                    self.a2 = (1. - libm::sqrtf(2. * v) * k + v * k * k) * norm;
-                    self.b1 = 2. * (k * k - 1.) * norm;
-                    self.b2 = (1. - libm::sqrtf(2.) * k + k * k) * norm;
+                    norm = 1. / (1. + SQRT_2 * k + k * k);
+                    c.a0 = (1. + sqrt_2v * k + v * k * k) * norm;
+                    c.a1 = 2. * (v * k * k - 1.) * norm;
+                    c.a2 = (1. - sqrt_2v * k + v * k * k) * norm;
+                    c.b1 = 2. * (k * k - 1.) * norm;
+                    c.b2 = (1. - SQRT_2 * k + k * k) * norm;
                 } else {
-                    norm = 1. / (1. + libm::sqrtf(2. * v) * k + v * k * k);
-                    self.a0 = (1. + libm::sqrtf(2.) * k + k * k) * norm;
-                    self.a1 = 2. * (k * k - 1.) * norm;
-                    self.a2 = (1. - libm::sqrtf(2.) * k + k * k) * norm;
-                    self.b1 = 2. * (v * k * k - 1.) * norm;
-                    self.b2 = (1. - libm::sqrtf(2. * v) * k + v * k * k) * norm;
+                    norm = 1. / (1. + sqrt_2v * k + v * k * k);
+                    c.a0 = (1. + SQRT_2 * k + k * k) * norm;
+                    c.a1 = 2. * (k * k - 1.) * norm;
+                    c.a2 = (1. - SQRT_2 * k + k * k) * norm;
+                    c.b1 = 2. * (v * k * k - 1.) * norm;
+                    c.b2 = (1. - sqrt_2v * k + v * k * k) * norm;
                 }
             }
             Highshelf => {
                 if peak_gain >= 0. {
-                    norm = 1. / (1. + libm::sqrtf(2.) * k + k * k);
-                    self.a0 = (v + libm::sqrtf(2. * v) * k + k * k) * norm;
-                    self.a1 = 2. * (k * k - v) * norm;
-                    self.a2 = (v - libm::sqrtf(2. * v) * k + k * k) * norm;
-                    self.b1 = 2. * (k * k - 1.) * norm;
-                    self.b2 = (1. - libm::sqrtf(2.) * k + k * k) * norm;
+                    norm = 1. / (1. + SQRT_2 * k + k * k);
+                    c.a0 = (v + sqrt_2v * k + k * k) * norm;
+                    c.a1 = 2. * (k * k - v) * norm;
+                    c.a2 = (v - sqrt_2v * k + k * k) * norm;
+                    c.b1 = 2. * (k * k - 1.) * norm;
+                    c.b2 = (1. - SQRT_2 * k + k * k) * norm;
                 } else {
-                    norm = 1. / (v + libm::sqrtf(2. * v) * k + k * k);
-                    self.a0 = (1. + libm::sqrtf(2.) * k + k * k) * norm;
-                    self.a1 = 2. * (k * k - 1.) * norm;
-                    self.a2 = (1. - libm::sqrtf(2.) * k + k * k) * norm;
-                    self.b1 = 2. * (k * k - v) * norm;
-                    self.b2 = (v - libm::sqrtf(2. * v) * k + k * k) * norm;
+                    norm = 1. / (v + sqrt_2v * k + k * k);
+                    c.a0 = (1. + SQRT_2 * k + k * k) * norm;
+                    c.a1 = 2. * (k * k - 1.) * norm;
+                    c.a2 = (1. - SQRT_2 * k + k * k) * norm;
+                    c.b1 = 2. * (k * k - v) * norm;
+                    c.b2 = (v - sqrt_2v * k + k * k) * norm;
                 }
             }
         }
+
+        c
     }
 }
 
+// Maps the roots of c0 + c1 s + c2 s^2 through z = e^(s * wc), returning the
+// monic polynomial in z^-1 that has them as roots.
+fn map_roots([c0, c1, c2]: [f32; 3], wc: f32) -> [f32; 3] {
+    if c2 != 0. {
+        let discriminant = c1 * c1 - 4. * c2 * c0;
+        let real = -c1 / (2. * c2);
+
+        if discriminant >= 0. {
+            let offset = libm::sqrtf(discriminant) / (2. * c2);
+            let z1 = libm::expf((real + offset) * wc);
+            let z2 = libm::expf((real - offset) * wc);
+            [1., -(z1 + z2), z1 * z2]
+        } else {
+            let imaginary = libm::sqrtf(-discriminant) / (2. * c2);
+            let radius = libm::expf(real * wc);
+            [
+                1.,
+                -2. * radius * libm::cosf(imaginary * wc),
+                radius * radius,
+            ]
+        }
+    } else if c1 != 0. {
+        [1., -libm::expf(-c0 / c1 * wc), 0.]
+    } else {
+        [1., 0., 0.]
+    }
+}
+
+fn analog_magnitude([c0, c1, c2]: [f32; 3], w: f32) -> f32 {
+    libm::hypotf(c0 - c2 * w * w, c1 * w)
+}
+
+fn digital_magnitude([c0, c1, c2]: [f32; 3], w: f32) -> f32 {
+    libm::hypotf(
+        c0 + c1 * libm::cosf(w) + c2 * libm::cosf(2. * w),
+        c1 * libm::sinf(w) + c2 * libm::sinf(2. * w),
+    )
+}
+
 pub struct Biquad {
     parameters: BiquadParameters,
     biquad_type: BiquadType,
@@ -158,6 +384,40 @@ impl Biquad {
         }
     }
 
+    /// Create a biquad from precomputed coefficients, such as those from
+    /// `BiquadCoefficients::bilinear`. Calling `set_type` or `set_params`
+    /// afterwards replaces them with a cookbook design.
+    pub const fn from_coefficients(coefficients: BiquadCoefficients) -> Self {
+        Self {
+            parameters: BiquadParameters {
+                cutoff: 0.5,
+                q: 0.707,
+                peak_gain: 0.,
+            },
+            biquad_type: BiquadType::Lowpass,
+            coefficients,
+            z1: 0.,
+            z2: 0.,
+        }
+    }
+
+    /// Like `new`, but the coefficients are computed with
+    /// `BiquadCoefficients::design` so it can be used to build a `const` or
+    /// `static` filter.
+    pub const fn new_const(biquad_type: BiquadType, parameters: BiquadParameters) -> Self {
+        Self {
+            parameters,
+            biquad_type,
+            coefficients: BiquadCoefficients::design(biquad_type, parameters),
+            z1: 0.,
+            z2: 0.,
+        }
+    }
+
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
+        self.coefficients = coefficients;
+    }
+
     pub fn set_type(&mut self, biquad_type: BiquadType) {
         self.biquad_type = biquad_type;
         self.coefficients.update(self.biquad_type, self.parameters);
@@ -172,6 +432,57 @@ impl Biquad {
         self.parameters = params;
         self.set_type(biquad_type);
     }
+
+    pub fn try_new(
+        biquad_type: BiquadType,
+        parameters: BiquadParameters,
+    ) -> Result<Self, BiquadError> {
+        let coefficients = Self::try_design(biquad_type, parameters)?;
+        Ok(Self {
+            parameters,
+            biquad_type,
+            coefficients,
+            z1: 0.,
+            z2: 0.,
+        })
+    }
+
+    /// Like `set_params`, but leaves the filter untouched if the parameters
+    /// are invalid.
+    pub fn try_set_params(&mut self, params: BiquadParameters) -> Result<(), BiquadError> {
+        self.coefficients = Self::try_design(self.biquad_type, params)?;
+        self.parameters = params;
+        Ok(())
+    }
+
+    /// Like `set_params`, but clamps the parameters into a valid range first.
+    /// Useful on the audio thread where errors can't be handled. If the
+    /// clamped design is still unstable the previous parameters and
+    /// coefficients are kept.
+    pub fn set_params_clamped(&mut self, params: BiquadParameters) {
+        let params = params.clamp();
+
+        if let Ok(coefficients) = Self::try_design(self.biquad_type, params) {
+            self.coefficients = coefficients;
+            self.parameters = params;
+        }
+    }
+
+    fn try_design(
+        biquad_type: BiquadType,
+        parameters: BiquadParameters,
+    ) -> Result<BiquadCoefficients, BiquadError> {
+        parameters.validate()?;
+
+        let mut coefficients = BiquadCoefficients::default();
+        coefficients.update(biquad_type, parameters);
+
+        if coefficients.is_stable() {
+            Ok(coefficients)
+        } else {
+            Err(BiquadError::Unstable)
+        }
+    }
 }
 
 impl Default for Biquad {
@@ -195,3 +506,240 @@ impl Node<f32, f32> for Biquad {
         out
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: BiquadCoefficients, b: BiquadCoefficients) {
+        let a = [a.a0, a.a1, a.a2, a.b1, a.b2];
+        let b = [b.a0, b.a1, b.a2, b.b1, b.b2];
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert!((a - b).abs() <= 1e-6 * b.abs().max(1.), "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_bilinear_matches_cookbook() {
+        let q = 0.707;
+        let parameters = BiquadParameters {
+            cutoff: 0.1,
+            q,
+            peak_gain: 0.,
+        };
+
+        let mut highpass = BiquadCoefficients::default();
+        highpass.update(BiquadType::Highpass, parameters);
+        assert_close(
+            BiquadCoefficients::bilinear(
+                AnalogPrototype {
+                    b0: 0.,
+                    b1: 0.,
+                    b2: 1.,
+                    a0: 1.,
+                    a1: 1. / q,
+                    a2: 1.,
+                },
+                0.1,
+            ),
+            highpass,
+        );
+
+        let mut bandpass = BiquadCoefficients::default();
+        bandpass.update(BiquadType::Bandpass, parameters);
+        assert_close(
+            BiquadCoefficients::bilinear(
+                AnalogPrototype {
+                    b0: 0.,
+                    b1: 1. / q,
+                    b2: 0.,
+                    a0: 1.,
+                    a1: 1. / q,
+                    a2: 1.,
+                },
+                0.1,
+            ),
+            bandpass,
+        );
+    }
+
+    #[test]
+    fn test_const_design_matches_runtime() {
+        use BiquadType::*;
+
+        const LOWPASS: Biquad = Biquad::new_const(
+            Lowpass,
+            BiquadParameters {
+                cutoff: 0.01,
+                q: 0.707,
+                peak_gain: 0.,
+            },
+        );
+
+        let runtime = Biquad::new(Lowpass, LOWPASS.parameters);
+        assert_close(LOWPASS.coefficients, runtime.coefficients);
+
+        let types = [
+            Lowpass, Highpass, Bandpass, Notch, Peak, Lowshelf, Highshelf,
+        ];
+
+        for &biquad_type in types.iter() {
+            for &cutoff in [0.001, 0.05, 0.2, 0.45].iter() {
+                for &peak_gain in [-12., -3., 0., 6., 24.].iter() {
+                    let parameters = BiquadParameters {
+                        cutoff,
+                        q: 1.2,
+                        peak_gain,
+                    };
+
+                    let mut runtime = BiquadCoefficients::default();
+                    runtime.update(biquad_type, parameters);
+
+                    assert_close(BiquadCoefficients::design(biquad_type, parameters), runtime);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let valid = BiquadParameters {
+            cutoff: 0.25,
+            q: 0.707,
+            peak_gain: 6.,
+        };
+
+        assert!(Biquad::try_new(BiquadType::Lowpass, valid).is_ok());
+
+        let check = |parameters: BiquadParameters, error: BiquadError| {
+            assert_eq!(
+                Biquad::try_new(BiquadType::Peak, parameters).err(),
+                Some(error)
+            );
+        };
+
+        check(
+            BiquadParameters {
+                cutoff: 0.5,
+                ..valid
+            },
+            BiquadError::InvalidCutoff,
+        );
+        check(
+            BiquadParameters {
+                cutoff: 0.,
+                ..valid
+            },
+            BiquadError::InvalidCutoff,
+        );
+        check(
+            BiquadParameters {
+                cutoff: f32::NAN,
+                ..valid
+            },
+            BiquadError::InvalidCutoff,
+        );
+        check(BiquadParameters { q: 0., ..valid }, BiquadError::InvalidQ);
+        check(
+            BiquadParameters {
+                q: f32::NAN,
+                ..valid
+            },
+            BiquadError::InvalidQ,
+        );
+        check(
+            BiquadParameters {
+                peak_gain: f32::INFINITY,
+                ..valid
+            },
+            BiquadError::InvalidPeakGain,
+        );
+
+        let mut biquad = Biquad::try_new(BiquadType::Lowpass, valid).unwrap();
+        assert!(biquad
+            .try_set_params(BiquadParameters { q: -1., ..valid })
+            .is_err());
+        assert_eq!(biquad.parameters.q, valid.q);
+    }
+
+    #[test]
+    fn test_typical_parameters_are_stable() {
+        use BiquadType::*;
+
+        let types = [
+            Lowpass, Highpass, Bandpass, Notch, Peak, Lowshelf, Highshelf,
+        ];
+
+        for &biquad_type in types.iter() {
+            for &cutoff in [0.001, 0.01, 0.1, 0.3, MAX_CUTOFF].iter() {
+                for &q in [MIN_Q, 0.5, 10.].iter() {
+                    for &peak_gain in [-24., 0., 24.].iter() {
+                        let parameters = BiquadParameters {
+                            cutoff,
+                            q,
+                            peak_gain,
+                        };
+                        assert!(Biquad::try_new(biquad_type, parameters).is_ok());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_clamped_parameters_are_stable() {
+        let mut biquad = Biquad::default();
+
+        let parameters = [
+            (0.7, -1.),
+            (f32::NAN, f32::NAN),
+            (-0.2, 0.),
+            (0., f32::MAX),
+        ];
+
+        for &(cutoff, q) in parameters.iter() {
+            biquad.set_params_clamped(BiquadParameters {
+                cutoff,
+                q,
+                peak_gain: f32::NAN,
+            });
+
+            assert!(biquad.parameters.validate().is_ok());
+            assert!(biquad.coefficients.is_stable());
+
+            for _ in 0..100 {
+                assert!(biquad.process(1.).is_finite());
+            }
+        }
+
+        // The sharpest, lowest design still clamps to a stable filter
+        assert_eq!(biquad.parameters.cutoff, MIN_CUTOFF);
+        assert_eq!(biquad.parameters.q, MAX_Q);
+    }
+
+    #[test]
+    fn test_matched_z_one_pole() {
+        let coefficients = BiquadCoefficients::matched_z(
+            AnalogPrototype {
+                b0: 1.,
+                b1: 0.,
+                b2: 0.,
+                a0: 1.,
+                a1: 1.,
+                a2: 0.,
+            },
+            0.05,
+        );
+
+        let pole = libm::expf(-2. * PI * 0.05);
+        assert!((coefficients.b1 + pole).abs() < 1e-6);
+        assert_eq!(coefficients.b2, 0.);
+
+        let mut biquad = Biquad::from_coefficients(coefficients);
+        let mut output = 0.;
+        for _ in 0..1000 {
+            output = biquad.process(1.);
+        }
+        assert!((output - 1.).abs() < 1e-4);
+    }
+}
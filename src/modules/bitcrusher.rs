@@ -0,0 +1,242 @@
+use super::biquad::{Biquad, BiquadParameters, BiquadType, MAX_CUTOFF};
+use super::{Node, Random};
+use libm;
+
+/// Reduces the bit depth and sample rate of a signal. Both can be set
+/// continuously, so they can be swept smoothly.
+///
+/// A pair of bitcrushers makes a stereo node, as with any `Node<f32, f32>`.
+/// Give them different seeds with `with_seed` so their dither isn't
+/// correlated.
+pub struct Bitcrusher {
+    bits: f32,
+    step: f32,
+    downsample: f32,
+    dither: bool,
+    anti_imaging: Option<Biquad>,
+
+    phase: f32,
+    held: f32,
+    random: Random,
+}
+
+impl Bitcrusher {
+    pub fn new() -> Self {
+        Self::with_seed(1)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut bitcrusher = Self {
+            bits: 24.,
+            step: 0.,
+            downsample: 1.,
+            dither: false,
+            anti_imaging: None,
+
+            phase: 0.,
+            held: 0.,
+            random: Random::new(seed),
+        };
+
+        bitcrusher.set_bits(24.);
+
+        bitcrusher
+    }
+
+    pub fn bits(&self) -> f32 {
+        self.bits
+    }
+
+    /// Set the bit depth, which needn't be a whole number. The signal is
+    /// quantised to steps of `2^(1 - bits)`, so 1 bit leaves -1, 0 and 1.
+    pub fn set_bits(&mut self, bits: f32) {
+        assert!(bits >= 1.);
+
+        self.bits = bits;
+        self.step = libm::exp2f(1. - bits);
+    }
+
+    pub fn downsample(&self) -> f32 {
+        self.downsample
+    }
+
+    /// Hold each sample for `factor` samples on average, which needn't be a
+    /// whole number.
+    pub fn set_downsample(&mut self, factor: f32) {
+        assert!(factor >= 1.);
+
+        self.downsample = factor;
+        self.update_anti_imaging();
+    }
+
+    pub fn dither(&self) -> bool {
+        self.dither
+    }
+
+    /// Add triangular dither of one step before quantising, trading
+    /// distortion for a noise floor.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+    }
+
+    pub fn anti_imaging(&self) -> bool {
+        self.anti_imaging.is_some()
+    }
+
+    /// Smooth the held steps with a lowpass at the reduced Nyquist frequency.
+    pub fn set_anti_imaging(&mut self, enabled: bool) {
+        self.anti_imaging = if enabled {
+            Some(Biquad::new(BiquadType::Lowpass, self.anti_imaging_params()))
+        } else {
+            None
+        };
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.;
+        self.held = 0.;
+        self.set_anti_imaging(self.anti_imaging());
+    }
+
+    fn anti_imaging_params(&self) -> BiquadParameters {
+        BiquadParameters {
+            cutoff: (0.5 / self.downsample).min(MAX_CUTOFF),
+            q: 0.707,
+            peak_gain: 0.,
+        }
+    }
+
+    fn update_anti_imaging(&mut self) {
+        let params = self.anti_imaging_params();
+
+        if let Some(filter) = self.anti_imaging.as_mut() {
+            filter.set_params(params);
+        }
+    }
+
+    fn quantise(&mut self, input: f32) -> f32 {
+        let mut input = input;
+
+        if self.dither {
+            input += (self.random.uniform() - self.random.uniform()) * self.step;
+        }
+
+        libm::roundf(input / self.step) * self.step
+    }
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node<f32, f32> for Bitcrusher {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        self.phase -= 1.;
+        if self.phase < 0. {
+            self.phase += self.downsample;
+            self.held = self.quantise(input);
+        }
+
+        match self.anti_imaging.as_mut() {
+            Some(filter) => filter.process(self.held),
+            None => self.held,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        let mut bitcrusher = Bitcrusher::new();
+        bitcrusher.set_bits(3.);
+
+        for n in 0..100 {
+            let input = libm::sinf(n as f32 * 0.1) * 0.9;
+            let output = bitcrusher.process(input);
+
+            assert_eq!(output, libm::roundf(input * 4.) / 4.);
+        }
+
+        // Halfway between 2 and 3 bits
+        bitcrusher.set_bits(2.5);
+        let step = libm::exp2f(-1.5);
+        assert_eq!(bitcrusher.process(0.4), step);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut bitcrusher = Bitcrusher::new();
+        bitcrusher.set_downsample(2.5);
+
+        let mut changes = 0;
+        let mut previous = -1.;
+        for n in 0..100 {
+            let output = bitcrusher.process(n as f32 / 1000.);
+            if output != previous {
+                changes += 1;
+                previous = output;
+            }
+        }
+
+        assert_eq!(changes, 40);
+    }
+
+    #[test]
+    fn test_dither_linearises() {
+        let mut bitcrusher = Bitcrusher::new();
+        bitcrusher.set_bits(4.);
+        let step = 0.125;
+
+        // Without dither anything under half a step disappears
+        let input = 0.3 * step;
+        assert_eq!(bitcrusher.process(input), 0.);
+
+        bitcrusher.set_dither(true);
+        let mut sum = 0.;
+        for _ in 0..100000 {
+            sum += bitcrusher.process(input);
+        }
+
+        let mean = sum / 100000.;
+        assert!((mean - input).abs() < 0.05 * step, "{}", mean);
+    }
+
+    #[test]
+    fn test_anti_imaging() {
+        let energy = |anti_imaging: bool| {
+            let mut bitcrusher = Bitcrusher::new();
+            bitcrusher.set_downsample(8.);
+            bitcrusher.set_anti_imaging(anti_imaging);
+
+            // The energy of the difference between samples, which is
+            // dominated by the steps
+            let (mut previous, mut energy) = (0., 0.);
+            for n in 0..1000 {
+                let output = bitcrusher.process(libm::sinf(n as f32 * 0.05));
+                energy += (output - previous) * (output - previous);
+                previous = output;
+            }
+            energy
+        };
+
+        let (filtered, stepped) = (energy(true), energy(false));
+        assert!(filtered < stepped / 4., "{} {}", filtered, stepped);
+    }
+
+    #[test]
+    fn test_stereo_pair() {
+        let mut stereo = (Bitcrusher::with_seed(1), Bitcrusher::with_seed(2));
+        stereo.0.set_bits(2.);
+        stereo.1.set_bits(8.);
+
+        let (left, right) = stereo.process((0.3, 0.3));
+        assert_eq!(left, 0.5);
+        assert_eq!(right, libm::roundf(0.3 * 128.) / 128.);
+    }
+}
@@ -0,0 +1,257 @@
+use super::{Complex, Fft, Node};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConvolutionLatency {
+    /// Every tap is convolved in the frequency domain, delaying the output by
+    /// half the FFT size.
+    Block,
+    /// The first block of taps is convolved directly in the time domain so
+    /// there is no added latency, at the cost of extra work per sample.
+    Zero,
+}
+
+/// Uniformly partitioned overlap-save convolution with an impulse response,
+/// using FFTs of size `FFT` and blocks of `FFT / 2` samples.
+///
+/// The spectra of each partition of the impulse response, and the spectra of
+/// past input blocks, are kept in buffers provided by the caller so that long
+/// impulse responses don't need to live on the stack. Both must hold at least
+/// `Convolver::partitions` entries.
+pub struct Convolver<'a, const FFT: usize> {
+    fft: Fft<FFT>,
+    spectra: &'a mut [[Complex; FFT]],
+    history: &'a mut [[Complex; FFT]],
+    partitions: usize,
+    newest: usize,
+
+    latency: ConvolutionLatency,
+    // Taps convolved directly, when running with zero latency
+    head: [f32; FFT],
+
+    // The previous and current input blocks, back to back
+    input: [f32; FFT],
+    // Only the first half is used
+    output: [f32; FFT],
+    scratch: [Complex; FFT],
+    position: usize,
+}
+
+impl<'a, const FFT: usize> Convolver<'a, FFT> {
+    pub const BLOCK: usize = FFT / 2;
+
+    /// How many partitions an impulse response of `length` samples needs.
+    pub const fn partitions(length: usize, latency: ConvolutionLatency) -> usize {
+        let block = FFT / 2;
+
+        let length = match latency {
+            ConvolutionLatency::Block => length,
+            ConvolutionLatency::Zero => length.saturating_sub(block),
+        };
+
+        length.div_ceil(block)
+    }
+
+    pub fn new(
+        impulse_response: &[f32],
+        spectra: &'a mut [[Complex; FFT]],
+        history: &'a mut [[Complex; FFT]],
+        latency: ConvolutionLatency,
+    ) -> Self {
+        assert!(FFT >= 2);
+
+        let mut convolver = Self {
+            fft: Fft::new(),
+            spectra,
+            history,
+            partitions: 0,
+            newest: 0,
+
+            latency,
+            head: [0.; FFT],
+
+            input: [0.; FFT],
+            output: [0.; FFT],
+            scratch: [Complex::default(); FFT],
+            position: 0,
+        };
+
+        convolver.load(impulse_response);
+
+        convolver
+    }
+
+    /// The number of samples the output is delayed by.
+    pub fn latency(&self) -> usize {
+        match self.latency {
+            ConvolutionLatency::Block => Self::BLOCK,
+            ConvolutionLatency::Zero => 0,
+        }
+    }
+
+    /// Replace the impulse response and clear any state.
+    pub fn load(&mut self, impulse_response: &[f32]) {
+        let block = Self::BLOCK;
+
+        self.partitions = Self::partitions(impulse_response.len(), self.latency);
+        assert!(self.spectra.len() >= self.partitions);
+        assert!(self.history.len() >= self.partitions);
+
+        self.head = [0.; FFT];
+
+        let tail = match self.latency {
+            ConvolutionLatency::Block => impulse_response,
+            ConvolutionLatency::Zero => {
+                let length = impulse_response.len().min(block);
+                self.head[..length].copy_from_slice(&impulse_response[..length]);
+                &impulse_response[length..]
+            }
+        };
+
+        for (spectrum, taps) in self.spectra.iter_mut().zip(tail.chunks(block)) {
+            let mut padded = [0.; FFT];
+            padded[..taps.len()].copy_from_slice(taps);
+            self.fft.forward_real(&padded, spectrum);
+        }
+
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        for spectrum in self.history[..self.partitions].iter_mut() {
+            *spectrum = [Complex::default(); FFT];
+        }
+
+        self.input = [0.; FFT];
+        self.output = [0.; FFT];
+        self.position = 0;
+        self.newest = 0;
+    }
+
+    fn process_block(&mut self) {
+        let block = Self::BLOCK;
+
+        if self.partitions > 0 {
+            self.fft.forward_real(&self.input, &mut self.scratch);
+
+            self.newest = (self.newest + 1) % self.partitions;
+            self.history[self.newest] = self.scratch;
+
+            // Multiply each partition with the input from as many blocks ago
+            self.scratch = [Complex::default(); FFT];
+            for (age, spectrum) in self.spectra[..self.partitions].iter().enumerate() {
+                let past = &self.history[(self.newest + self.partitions - age) % self.partitions];
+
+                for ((bin, a), b) in self
+                    .scratch
+                    .iter_mut()
+                    .zip(past.iter())
+                    .zip(spectrum.iter())
+                {
+                    *bin += *a * *b;
+                }
+            }
+
+            let mut result = [0.; FFT];
+            self.fft.inverse_real(&self.scratch, &mut result);
+
+            // Only the second half is free of circular wrap around
+            self.output[..block].copy_from_slice(&result[block..]);
+        }
+
+        self.input.copy_within(block.., 0);
+    }
+}
+
+impl<'a, const FFT: usize> Node<f32, f32> for Convolver<'a, FFT> {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let block = Self::BLOCK;
+        let current = block + self.position;
+
+        self.input[current] = input;
+
+        let mut output = self.output[self.position];
+
+        if self.latency == ConvolutionLatency::Zero {
+            for (tap, sample) in self.head[..block]
+                .iter()
+                .zip(self.input[current + 1 - block..=current].iter().rev())
+            {
+                output += tap * sample;
+            }
+        }
+
+        self.position += 1;
+        if self.position == block {
+            self.position = 0;
+            self.process_block();
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTH: usize = 300;
+
+    fn noise(seed: &mut u32) -> f32 {
+        *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (*seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+    }
+
+    fn compare(latency: ConvolutionLatency) {
+        let mut seed = 7;
+
+        let mut impulse_response = [0.; LENGTH];
+        for tap in impulse_response.iter_mut() {
+            *tap = noise(&mut seed);
+        }
+
+        let mut spectra = [[Complex::default(); 64]; 10];
+        let mut history = [[Complex::default(); 64]; 10];
+        assert!(Convolver::<64>::partitions(LENGTH, latency) <= 10);
+
+        let mut convolver: Convolver<64> =
+            Convolver::new(&impulse_response, &mut spectra, &mut history, latency);
+        let delay = convolver.latency();
+
+        let mut input = [0.; 1000];
+        for sample in input.iter_mut() {
+            *sample = noise(&mut seed);
+        }
+
+        for (n, sample) in input.iter().enumerate() {
+            let output = convolver.process(*sample);
+
+            let mut expected = 0.;
+            if n >= delay {
+                for (m, tap) in impulse_response.iter().enumerate() {
+                    if m <= n - delay {
+                        expected += tap * input[n - delay - m];
+                    }
+                }
+            }
+
+            assert!(
+                (output - expected).abs() < 1e-4,
+                "{} {} {}",
+                n,
+                output,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_latency_matches_direct() {
+        compare(ConvolutionLatency::Block);
+    }
+
+    #[test]
+    fn test_zero_latency_matches_direct() {
+        compare(ConvolutionLatency::Zero);
+    }
+}
@@ -3,15 +3,76 @@ use super::{Node, ReadableNode};
 pub struct DelayLine<const N: usize> {
     buffer: [f32; N],
     index: usize,
+    length: usize,
 }
 
 impl<const N: usize> DelayLine<N> {
     pub fn new() -> Self {
+        Self::with_length(N)
+    }
+
+    /// Create a delay line that only uses the first `length` samples of its
+    /// buffer, for when the delay is only known at runtime.
+    pub fn with_length(length: usize) -> Self {
+        assert!(length > 0 && length <= N);
+
         Self {
             buffer: [0.; N],
             index: 0,
+            length,
         }
     }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Change how much of the buffer is used. Useful for delays that are
+    /// modulated or set by the user, like a pre-delay.
+    pub fn set_length(&mut self, length: usize) {
+        assert!(length > 0 && length <= N);
+
+        self.length = length;
+        if self.index >= length {
+            self.index = 0;
+        }
+    }
+
+    /// Fill the buffer with silence.
+    pub fn clear(&mut self) {
+        self.buffer = [0.; N];
+        self.index = 0;
+    }
+
+    /// Read the input from `delay` samples before the most recently processed
+    /// one, so `tap(0)` is the last input. `delay` must be less than the length.
+    #[inline]
+    pub fn tap(&self, delay: usize) -> f32 {
+        debug_assert!(delay < self.length);
+        self.buffer[(self.index + self.length - 1 - delay) % self.length]
+    }
+
+    /// Like `tap`, but linearly interpolates between samples for fractional
+    /// delays. `delay` must be less than the length minus one.
+    #[inline]
+    pub fn tap_linear(&self, delay: f32) -> f32 {
+        interpolate_linear(delay, |index| self.tap(index))
+    }
+}
+
+// Interpolates between `sample(n)` and `sample(n + 1)` for a fractional
+// `position` between them, only reading the second when it's needed.
+#[inline]
+pub(crate) fn interpolate_linear(position: f32, sample: impl Fn(usize) -> f32) -> f32 {
+    let whole = position as usize;
+    let fraction = position - whole as f32;
+
+    let a = sample(whole);
+    if fraction > 0. {
+        a + (sample(whole + 1) - a) * fraction
+    } else {
+        a
+    }
 }
 
 impl<const N: usize> Node<f32, f32> for DelayLine<N> {
@@ -19,7 +80,7 @@ impl<const N: usize> Node<f32, f32> for DelayLine<N> {
     fn process(&mut self, input: f32) -> f32 {
         self.buffer[self.index] = input;
 
-        if (self.index + 1) < self.buffer.len() {
+        if (self.index + 1) < self.length {
             self.index += 1;
         } else {
             self.index = 0
@@ -48,4 +109,32 @@ mod tests {
             assert_eq!(x, &f32::default());
         }
     }
+
+    #[test]
+    fn test_tap() {
+        let mut delay: DelayLine<4> = DelayLine::new();
+        for x in 1..=6 {
+            delay.process(x as f32);
+        }
+        assert_eq!(delay.tap(0), 6.0);
+        assert_eq!(delay.tap(1), 5.0);
+        assert_eq!(delay.tap(3), 3.0);
+        assert_eq!(delay.tap_linear(1.25), 4.75);
+    }
+
+    #[test]
+    fn test_with_length() {
+        let mut delay: DelayLine<10> = DelayLine::with_length(3);
+        assert_eq!(delay.process(1.0), 0.0);
+        assert_eq!(delay.process(0.0), 0.0);
+        assert_eq!(delay.process(0.0), 1.0);
+        assert_eq!(delay.process(0.0), 0.0);
+
+        delay.process(1.0);
+        delay.clear();
+        assert_eq!(delay.length(), 3);
+        for _ in 0..3 {
+            assert_eq!(delay.process(0.0), 0.0);
+        }
+    }
 }
@@ -0,0 +1,116 @@
+use super::{DelayLine, Node};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EarlyTap {
+    /// Delay in samples, must be less than the `EarlyReflections` size.
+    pub delay: usize,
+    pub left: f32,
+    pub right: f32,
+}
+
+const fn moorer_tap(delay: usize, gain: f32, left: bool) -> EarlyTap {
+    let (left, right) = if left {
+        (gain, gain * 0.6)
+    } else {
+        (gain * 0.6, gain)
+    };
+
+    EarlyTap { delay, left, right }
+}
+
+/// Moorer's 18 tap early reflection pattern at 44.1kHz, alternating between
+/// favouring the left and right channels.
+pub const MOORER_TAPS_44100: [EarlyTap; 18] = [
+    moorer_tap(190, 0.841, true),
+    moorer_tap(948, 0.504, false),
+    moorer_tap(992, 0.491, true),
+    moorer_tap(1182, 0.379, false),
+    moorer_tap(1191, 0.380, true),
+    moorer_tap(1314, 0.346, false),
+    moorer_tap(2020, 0.289, true),
+    moorer_tap(2139, 0.272, false),
+    moorer_tap(2523, 0.192, true),
+    moorer_tap(2589, 0.193, false),
+    moorer_tap(2624, 0.217, true),
+    moorer_tap(2699, 0.181, false),
+    moorer_tap(3118, 0.180, true),
+    moorer_tap(3122, 0.181, false),
+    moorer_tap(3202, 0.176, true),
+    moorer_tap(3268, 0.142, false),
+    moorer_tap(3321, 0.167, true),
+    moorer_tap(3515, 0.134, false),
+];
+
+/// A tapped delay line that turns a mono input into a stereo pattern of
+/// discrete reflections.
+pub struct EarlyReflections<const N: usize, const TAPS: usize> {
+    delay_line: DelayLine<N>,
+    taps: [EarlyTap; TAPS],
+}
+
+impl<const N: usize, const TAPS: usize> EarlyReflections<N, TAPS> {
+    pub fn new(taps: [EarlyTap; TAPS]) -> Self {
+        let mut early = Self {
+            delay_line: DelayLine::new(),
+            taps,
+        };
+        early.set_taps(taps);
+        early
+    }
+
+    pub fn taps(&self) -> &[EarlyTap; TAPS] {
+        &self.taps
+    }
+
+    pub fn set_taps(&mut self, taps: [EarlyTap; TAPS]) {
+        for tap in taps.iter() {
+            assert!(tap.delay < N);
+        }
+
+        self.taps = taps;
+    }
+}
+
+impl<const N: usize, const TAPS: usize> Node<f32, (f32, f32)> for EarlyReflections<N, TAPS> {
+    #[inline]
+    fn process(&mut self, input: f32) -> (f32, f32) {
+        self.delay_line.process(input);
+
+        let mut output = (0., 0.);
+
+        for tap in self.taps.iter() {
+            let delayed = self.delay_line.tap(tap.delay);
+            output.0 += delayed * tap.left;
+            output.1 += delayed * tap.right;
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taps() {
+        let mut early: EarlyReflections<8, 2> = EarlyReflections::new([
+            EarlyTap {
+                delay: 0,
+                left: 1.0,
+                right: 0.5,
+            },
+            EarlyTap {
+                delay: 3,
+                left: 0.25,
+                right: 0.75,
+            },
+        ]);
+
+        assert_eq!(early.process(1.0), (1.0, 0.5));
+        assert_eq!(early.process(0.0), (0.0, 0.0));
+        assert_eq!(early.process(0.0), (0.0, 0.0));
+        assert_eq!(early.process(0.0), (0.25, 0.75));
+        assert_eq!(early.process(0.0), (0.0, 0.0));
+    }
+}
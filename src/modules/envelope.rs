@@ -0,0 +1,571 @@
+use super::{Node, ReadableNode};
+use libm;
+
+// How far an exponential segment's underlying decay gets, in time constants,
+// before it is rescaled to land exactly on its target.
+const EXPONENTIAL_SHAPE: f32 = 5.;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// Moves quickly at first and slows towards the target, like the charging
+    /// and discharging of an analog envelope.
+    Exponential,
+}
+
+// Moves from one level to another over a whole number of samples.
+#[derive(Copy, Clone)]
+struct Ramp {
+    start: f32,
+    end: f32,
+    length: u32,
+    position: u32,
+    curve: Curve,
+    remaining: f32,
+    factor: f32,
+}
+
+impl Ramp {
+    fn new(start: f32, end: f32, length: u32, curve: Curve) -> Self {
+        Self {
+            start,
+            end,
+            length,
+            position: 0,
+            curve,
+            remaining: 1.,
+            factor: libm::expf(-EXPONENTIAL_SHAPE / length as f32),
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> f32 {
+        self.position += 1;
+        if self.finished() {
+            return self.end;
+        }
+
+        let progress = match self.curve {
+            Curve::Linear => self.position as f32 / self.length as f32,
+            Curve::Exponential => {
+                self.remaining *= self.factor;
+                (1. - self.remaining) / (1. - libm::expf(-EXPONENTIAL_SHAPE))
+            }
+        };
+
+        self.start + (self.end - self.start) * progress
+    }
+
+    fn finished(&self) -> bool {
+        self.position >= self.length
+    }
+}
+
+fn samples(time: f32, sample_rate: f32) -> u32 {
+    ((time * sample_rate / 1000.) as u32).max(1)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Trigger {
+    /// `trigger` restarts the attack from the current level.
+    Retrigger,
+    /// `trigger` is ignored, so overlapping notes carry on from wherever the
+    /// envelope is.
+    Legato,
+}
+
+/// An attack, decay, sustain and release envelope, driven by the gate passed
+/// to `process`. Times are in milliseconds, and each stage takes its full time
+/// from wherever the previous one left off, so nothing jumps.
+pub struct Adsr {
+    sample_rate: f32,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    curve: Curve,
+    trigger: Trigger,
+
+    gate: bool,
+    stage: Stage,
+    ramp: Ramp,
+    output: f32,
+}
+
+impl Adsr {
+    pub fn new(sample_rate: f32) -> Self {
+        assert!(sample_rate > 0.);
+
+        Self {
+            sample_rate,
+            attack: 10.,
+            decay: 100.,
+            sustain: 0.7,
+            release: 200.,
+            curve: Curve::Exponential,
+            trigger: Trigger::Retrigger,
+
+            gate: false,
+            stage: Stage::Idle,
+            ramp: Ramp::new(0., 0., 1, Curve::Linear),
+            output: 0.,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Only affects stages started after the change.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        assert!(sample_rate > 0.);
+
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn attack(&self) -> f32 {
+        self.attack
+    }
+
+    pub fn set_attack(&mut self, time: f32) {
+        assert!(time >= 0.);
+
+        self.attack = time;
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    pub fn set_decay(&mut self, time: f32) {
+        assert!(time >= 0.);
+
+        self.decay = time;
+    }
+
+    pub fn sustain(&self) -> f32 {
+        self.sustain
+    }
+
+    pub fn set_sustain(&mut self, level: f32) {
+        assert!((0. ..=1.).contains(&level));
+
+        self.sustain = level;
+    }
+
+    pub fn release(&self) -> f32 {
+        self.release
+    }
+
+    pub fn set_release(&mut self, time: f32) {
+        assert!(time >= 0.);
+
+        self.release = time;
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    pub fn trigger_mode(&self) -> Trigger {
+        self.trigger
+    }
+
+    pub fn set_trigger_mode(&mut self, trigger: Trigger) {
+        self.trigger = trigger;
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Idle
+    }
+
+    /// Start a new note while the gate is already held, depending on the
+    /// trigger mode.
+    pub fn trigger(&mut self) {
+        if self.gate && self.trigger == Trigger::Retrigger {
+            self.start(Stage::Attack);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.gate = false;
+        self.stage = Stage::Idle;
+        self.output = 0.;
+    }
+
+    fn start(&mut self, stage: Stage) {
+        let (target, time) = match stage {
+            Stage::Attack => (1., self.attack),
+            Stage::Decay => (self.sustain, self.decay),
+            Stage::Release => (0., self.release),
+            Stage::Idle | Stage::Sustain => (self.output, 0.),
+        };
+
+        self.stage = stage;
+        self.ramp = Ramp::new(
+            self.output,
+            target,
+            samples(time, self.sample_rate),
+            self.curve,
+        );
+    }
+}
+
+impl Node<bool, f32> for Adsr {
+    #[inline]
+    fn process(&mut self, gate: bool) -> f32 {
+        if gate != self.gate {
+            self.gate = gate;
+            self.start(if gate { Stage::Attack } else { Stage::Release });
+        }
+
+        self.output = match self.stage {
+            Stage::Idle => 0.,
+            Stage::Sustain => self.sustain,
+            Stage::Attack | Stage::Decay | Stage::Release => self.ramp.next(),
+        };
+
+        if self.ramp.finished() {
+            match self.stage {
+                Stage::Attack => self.start(Stage::Decay),
+                Stage::Decay => self.stage = Stage::Sustain,
+                Stage::Release => self.stage = Stage::Idle,
+                Stage::Idle | Stage::Sustain => {}
+            }
+        }
+
+        self.output
+    }
+}
+
+impl ReadableNode<f32> for Adsr {
+    #[inline]
+    fn read(&self) -> f32 {
+        self.output
+    }
+}
+
+/// A breakpoint of an `Envelope`, reached `time` milliseconds after the
+/// previous one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub level: f32,
+    pub time: f32,
+    pub curve: Curve,
+}
+
+/// An envelope moving through `SEGMENTS` breakpoints, starting whenever the
+/// gate passed to `process` goes high, and holding the last level once done.
+///
+/// With a loop set, the segments from the start of the loop to its end repeat
+/// for as long as the gate is held. Releasing the gate moves straight on to
+/// the segment after the loop, so anything after it acts as a release.
+/// Holding the gate high for good makes for a free running modulator.
+pub struct Envelope<const SEGMENTS: usize> {
+    segments: [Segment; SEGMENTS],
+    loop_points: Option<(usize, usize)>,
+    sample_rate: f32,
+
+    gate: bool,
+    index: Option<usize>,
+    ramp: Ramp,
+    output: f32,
+}
+
+impl<const SEGMENTS: usize> Envelope<SEGMENTS> {
+    pub fn new(sample_rate: f32, segments: [Segment; SEGMENTS]) -> Self {
+        assert!(sample_rate > 0.);
+
+        for segment in segments.iter() {
+            assert!(segment.time >= 0.);
+        }
+
+        Self {
+            segments,
+            loop_points: None,
+            sample_rate,
+
+            gate: false,
+            index: None,
+            ramp: Ramp::new(0., 0., 1, Curve::Linear),
+            output: 0.,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Only affects segments started after the change.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        assert!(sample_rate > 0.);
+
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn segments(&self) -> &[Segment; SEGMENTS] {
+        &self.segments
+    }
+
+    pub fn set_segment(&mut self, index: usize, segment: Segment) {
+        assert!(segment.time >= 0.);
+
+        self.segments[index] = segment;
+    }
+
+    pub fn loop_points(&self) -> Option<(usize, usize)> {
+        self.loop_points
+    }
+
+    /// Loop from the start of segment `start` to the end of segment `end`,
+    /// inclusive, while the gate is held.
+    pub fn set_loop_points(&mut self, loop_points: Option<(usize, usize)>) {
+        if let Some((start, end)) = loop_points {
+            assert!(start <= end && end < SEGMENTS);
+        }
+
+        self.loop_points = loop_points;
+    }
+
+    /// Which segment the envelope is moving through, if any.
+    pub fn segment(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.index.is_some()
+    }
+
+    pub fn reset(&mut self) {
+        self.gate = false;
+        self.index = None;
+        self.output = 0.;
+    }
+
+    fn start(&mut self, index: usize) {
+        if index >= SEGMENTS {
+            self.index = None;
+            return;
+        }
+
+        let segment = self.segments[index];
+        self.index = Some(index);
+        self.ramp = Ramp::new(
+            self.output,
+            segment.level,
+            samples(segment.time, self.sample_rate),
+            segment.curve,
+        );
+    }
+}
+
+impl<const SEGMENTS: usize> Node<bool, f32> for Envelope<SEGMENTS> {
+    #[inline]
+    fn process(&mut self, gate: bool) -> f32 {
+        if gate != self.gate {
+            self.gate = gate;
+
+            if gate {
+                self.start(0);
+            } else if let (Some(index), Some((start, end))) = (self.index, self.loop_points) {
+                if index >= start && index <= end {
+                    self.start(end + 1);
+                }
+            }
+        }
+
+        if let Some(index) = self.index {
+            self.output = self.ramp.next();
+
+            if self.ramp.finished() {
+                match self.loop_points {
+                    Some((start, end)) if self.gate && index == end => self.start(start),
+                    _ => self.start(index + 1),
+                }
+            }
+        }
+
+        self.output
+    }
+}
+
+impl<const SEGMENTS: usize> ReadableNode<f32> for Envelope<SEGMENTS> {
+    #[inline]
+    fn read(&self) -> f32 {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // At 1kHz each millisecond is one sample
+    const RATE: f32 = 1000.;
+
+    fn linear_adsr() -> Adsr {
+        let mut adsr = Adsr::new(RATE);
+        adsr.set_curve(Curve::Linear);
+        adsr.set_attack(10.);
+        adsr.set_decay(10.);
+        adsr.set_sustain(0.5);
+        adsr.set_release(20.);
+        adsr
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} {}", a, b);
+    }
+
+    #[test]
+    fn test_adsr() {
+        let mut adsr = linear_adsr();
+        assert_eq!(adsr.process(false), 0.);
+        assert_eq!(adsr.stage(), Stage::Idle);
+
+        for n in 1..=10 {
+            assert_close(adsr.process(true), n as f32 / 10.);
+        }
+        for n in 1..=10 {
+            assert_close(adsr.process(true), 1. - 0.5 * n as f32 / 10.);
+        }
+        for _ in 0..100 {
+            assert_eq!(adsr.process(true), 0.5);
+        }
+        assert_eq!(adsr.stage(), Stage::Sustain);
+
+        for n in 1..=20 {
+            assert_close(adsr.process(false), 0.5 - 0.5 * n as f32 / 20.);
+        }
+        assert!(!adsr.is_active());
+        assert_eq!(adsr.process(false), 0.);
+    }
+
+    #[test]
+    fn test_release_during_attack() {
+        let mut adsr = linear_adsr();
+        for _ in 0..5 {
+            adsr.process(true);
+        }
+
+        // Releases from halfway up, taking the full release time
+        for n in 1..=20 {
+            assert_close(adsr.process(false), 0.5 - 0.5 * n as f32 / 20.);
+        }
+        assert_eq!(adsr.stage(), Stage::Idle);
+    }
+
+    #[test]
+    fn test_exponential() {
+        let mut adsr = linear_adsr();
+        adsr.set_curve(Curve::Exponential);
+
+        let mut previous = 0.;
+        for n in 1..=10 {
+            let output = adsr.process(true);
+
+            // Rises faster than a straight line, but never overshoots
+            assert!(output > previous && output <= 1.);
+            assert!(output >= n as f32 / 10. - 1e-6);
+            previous = output;
+        }
+        assert_eq!(previous, 1.);
+
+        for _ in 0..10 {
+            let output = adsr.process(true);
+            assert!(output < previous && output >= 0.5);
+            previous = output;
+        }
+        assert_eq!(adsr.stage(), Stage::Sustain);
+    }
+
+    #[test]
+    fn test_trigger_modes() {
+        let mut adsr = linear_adsr();
+        for _ in 0..30 {
+            adsr.process(true);
+        }
+
+        adsr.set_trigger_mode(Trigger::Legato);
+        adsr.trigger();
+        assert_eq!(adsr.process(true), 0.5);
+
+        adsr.set_trigger_mode(Trigger::Retrigger);
+        adsr.trigger();
+        assert_eq!(adsr.stage(), Stage::Attack);
+
+        // Picks up from the sustain level
+        for n in 1..=10 {
+            assert_close(adsr.process(true), 0.5 + 0.5 * n as f32 / 10.);
+        }
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let mut adsr = linear_adsr();
+        adsr.set_sample_rate(RATE * 2.);
+
+        for n in 1..=20 {
+            assert_close(adsr.process(true), n as f32 / 20.);
+        }
+    }
+
+    #[test]
+    fn test_envelope_loop() {
+        let segment = |level, time| Segment {
+            level,
+            time,
+            curve: Curve::Linear,
+        };
+
+        let mut envelope = Envelope::new(
+            RATE,
+            [
+                segment(1., 4.),
+                segment(0.2, 4.),
+                segment(0.6, 2.),
+                segment(0., 5.),
+            ],
+        );
+        envelope.set_loop_points(Some((1, 2)));
+
+        let mut outputs = [0.; 14];
+        for output in outputs.iter_mut() {
+            *output = envelope.process(true);
+        }
+        let expected = [
+            0.25, 0.5, 0.75, 1., 0.8, 0.6, 0.4, 0.2, 0.4, 0.6, 0.5, 0.4, 0.3, 0.2,
+        ];
+        for (output, expected) in outputs.iter().zip(expected.iter()) {
+            assert_close(*output, *expected);
+        }
+
+        // Leaves the loop for the final segment
+        envelope.process(true);
+        envelope.process(true);
+        assert_eq!(envelope.segment(), Some(1));
+        for n in 1..=5 {
+            assert_close(envelope.process(false), 0.6 - 0.6 * n as f32 / 5.);
+        }
+
+        assert!(!envelope.is_active());
+        assert_eq!(envelope.process(false), 0.);
+    }
+}
@@ -0,0 +1,263 @@
+use super::{DelayLine, Node, OnePole, ReadableNode};
+use libm;
+
+// The level reported in decibels for silence.
+const MIN_DB: f32 = -120.;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Detection {
+    /// Follows the rectified signal.
+    Peak,
+    /// Follows the mean square, averaged by the attack and release smoothing.
+    Rms,
+    /// The mean square over a sliding window of exactly `window` samples,
+    /// then smoothed. Free of ripple when the window holds whole cycles.
+    TrueRms,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LevelScale {
+    Linear,
+    Decibels,
+}
+
+/// Follows the level of a signal, rising with the attack time and falling
+/// with the release time, using the same one-pole smoothing as the damping in
+/// `LowpassFeedbackComb`. `WINDOW` is the longest window for `TrueRms`
+/// detection.
+///
+/// The window's buffer is kept whatever the detection, so that it can be
+/// changed at runtime, which means `WINDOW` must be at least 1. Followers that
+/// never use `TrueRms` can be an `EnvelopeFollower<1>`, keeping one sample.
+pub struct EnvelopeFollower<const WINDOW: usize> {
+    sample_rate: f32,
+    detection: Detection,
+    scale: LevelScale,
+    attack: f32,
+    release: f32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+
+    smoother: OnePole,
+    window: DelayLine<WINDOW>,
+    sum: f64,
+    output: f32,
+}
+
+impl<const WINDOW: usize> EnvelopeFollower<WINDOW> {
+    pub fn new(sample_rate: f32, detection: Detection) -> Self {
+        assert!(WINDOW > 0);
+        assert!(sample_rate > 0.);
+
+        let mut follower = Self {
+            sample_rate,
+            detection,
+            scale: LevelScale::Linear,
+            attack: 0.,
+            release: 0.,
+            attack_coefficient: 0.,
+            release_coefficient: 0.,
+
+            smoother: OnePole::new(0.),
+            window: DelayLine::new(),
+            sum: 0.,
+            output: 0.,
+        };
+
+        follower.set_attack(10.);
+        follower.set_release(100.);
+
+        follower
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        assert!(sample_rate > 0.);
+
+        self.sample_rate = sample_rate;
+        self.set_attack(self.attack);
+        self.set_release(self.release);
+    }
+
+    pub fn detection(&self) -> Detection {
+        self.detection
+    }
+
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detection = detection;
+        self.reset();
+    }
+
+    pub fn scale(&self) -> LevelScale {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: LevelScale) {
+        self.scale = scale;
+    }
+
+    pub fn attack(&self) -> f32 {
+        self.attack
+    }
+
+    /// Set the time in milliseconds to cover `1 - 1/e` of a rise in level.
+    pub fn set_attack(&mut self, time: f32) {
+        assert!(time >= 0.);
+
+        self.attack = time;
+        self.attack_coefficient = OnePole::time_coefficient(time, self.sample_rate);
+    }
+
+    pub fn release(&self) -> f32 {
+        self.release
+    }
+
+    /// Set the time in milliseconds to cover `1 - 1/e` of a fall in level.
+    pub fn set_release(&mut self, time: f32) {
+        assert!(time >= 0.);
+
+        self.release = time;
+        self.release_coefficient = OnePole::time_coefficient(time, self.sample_rate);
+    }
+
+    pub fn window(&self) -> usize {
+        self.window.length()
+    }
+
+    /// Set the length of the `TrueRms` window in samples, up to `WINDOW`.
+    pub fn set_window(&mut self, length: usize) {
+        self.window.set_length(length);
+        self.window.clear();
+        self.sum = 0.;
+    }
+
+    /// The level in linear terms, whatever the output scale.
+    pub fn level(&self) -> f32 {
+        match self.detection {
+            Detection::Peak => self.smoother.read(),
+            Detection::Rms | Detection::TrueRms => libm::sqrtf(self.smoother.read()),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.smoother.reset();
+        self.window.clear();
+        self.sum = 0.;
+        self.output = 0.;
+    }
+}
+
+impl<const WINDOW: usize> Node<f32, f32> for EnvelopeFollower<WINDOW> {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let detected = match self.detection {
+            Detection::Peak => input.abs(),
+            Detection::Rms => input * input,
+            Detection::TrueRms => {
+                let square = input * input;
+                let leaving = self.window.read();
+                self.window.process(square);
+
+                // Rounding can leave the sum just below zero once the window
+                // falls silent
+                self.sum = (self.sum + square as f64 - leaving as f64).max(0.);
+                (self.sum / self.window.length() as f64) as f32
+            }
+        };
+
+        let coefficient = if detected > self.smoother.read() {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.smoother.set_coefficient(coefficient);
+        self.smoother.process(detected);
+
+        let level = self.level();
+        self.output = match self.scale {
+            LevelScale::Linear => level,
+            LevelScale::Decibels if level > 0. => (20. * libm::log10f(level)).max(MIN_DB),
+            LevelScale::Decibels => MIN_DB,
+        };
+
+        self.output
+    }
+}
+
+impl<const WINDOW: usize> ReadableNode<f32> for EnvelopeFollower<WINDOW> {
+    #[inline]
+    fn read(&self) -> f32 {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+
+    fn sine(amplitude: f32, n: usize) -> f32 {
+        // 100 samples per cycle
+        amplitude * libm::sinf(2. * PI * (n % 100) as f32 / 100.)
+    }
+
+    #[test]
+    fn test_peak_times() {
+        let mut follower: EnvelopeFollower<1> = EnvelopeFollower::new(1000., Detection::Peak);
+        follower.set_attack(10.);
+        follower.set_release(50.);
+
+        for _ in 0..10 {
+            follower.process(-1.);
+        }
+        let risen = 1. - libm::expf(-1.);
+        assert!((follower.read() - risen).abs() < 1e-4);
+
+        for _ in 0..50 {
+            follower.process(0.);
+        }
+        assert!((follower.read() - risen * libm::expf(-1.)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_rms() {
+        let mut rms: EnvelopeFollower<1> = EnvelopeFollower::new(1000., Detection::Rms);
+        rms.set_attack(500.);
+        rms.set_release(500.);
+
+        let mut true_rms: EnvelopeFollower<200> = EnvelopeFollower::new(1000., Detection::TrueRms);
+        true_rms.set_window(100);
+        true_rms.set_attack(0.);
+        true_rms.set_release(0.);
+
+        for n in 0..10000 {
+            let (a, b) = (rms.process(sine(0.5, n)), true_rms.process(sine(0.5, n)));
+
+            if n > 5000 {
+                assert!((a - 0.5 / libm::sqrtf(2.)).abs() < 0.01, "{}", a);
+            }
+            if n >= 100 {
+                assert!((b - 0.5 / libm::sqrtf(2.)).abs() < 1e-5, "{}", b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decibels() {
+        let mut follower: EnvelopeFollower<1> = EnvelopeFollower::new(1000., Detection::Peak);
+        follower.set_scale(LevelScale::Decibels);
+        follower.set_attack(0.);
+        follower.set_release(1000.);
+
+        assert_eq!(follower.process(0.), MIN_DB);
+
+        for n in 0..200 {
+            follower.process(sine(0.5, n));
+        }
+        assert!((follower.read() + 6.02).abs() < 0.1, "{}", follower.read());
+        assert!((follower.level() - 0.5).abs() < 0.01);
+    }
+}
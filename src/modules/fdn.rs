@@ -0,0 +1,302 @@
+use super::{DelayLine, Node};
+use core::f32::consts::PI;
+use libm;
+
+/// How the outputs of each line are fed back into every other line. Both are
+/// orthogonal, so the network is lossless apart from the decay filters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FdnMatrix {
+    Householder,
+    /// Only usable when the number of lines is a power of two.
+    Hadamard,
+}
+
+/// Mutually prime delay lengths that work well for an 8 line network at
+/// 44.1kHz.
+pub const FDN_LENGTHS_44100: [usize; 8] = [1433, 1601, 1867, 2053, 2251, 2399, 2617, 2897];
+
+// Per line decay filter, y[n] = b * x[n] + a * y[n - 1]. The gain at DC is
+// b / (1 - a) and at Nyquist is b / (1 + a).
+#[derive(Copy, Clone, Default)]
+struct DecayFilter {
+    a: f32,
+    b: f32,
+    state: f32,
+}
+
+impl DecayFilter {
+    fn set_gains(&mut self, dc: f32, nyquist: f32) {
+        self.a = (dc - nyquist) / (dc + nyquist);
+        self.b = 2. * dc * nyquist / (dc + nyquist);
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        self.state = self.b * input + self.a * self.state;
+        self.state
+    }
+}
+
+/// A feedback delay network reverb with `LINES` delay lines, each able to hold
+/// up to `SIZE` samples. Even lines are fed from and sum into the left
+/// channel, odd lines the right.
+pub struct Fdn<const LINES: usize, const SIZE: usize> {
+    lines: [DelayLine<SIZE>; LINES],
+    lengths: [usize; LINES],
+    filters: [DecayFilter; LINES],
+    phases: [f32; LINES],
+
+    matrix: FdnMatrix,
+    sample_rate: f32,
+    rt60: (f32, f32),
+    modulation_depth: f32,
+    modulation_rate: f32,
+    output_gain: f32,
+    wet: f32,
+    dry: f32,
+}
+
+impl<const LINES: usize, const SIZE: usize> Fdn<LINES, SIZE> {
+    pub fn new(sample_rate: f32, lengths: [usize; LINES]) -> Self {
+        assert!(LINES >= 2);
+
+        for &length in lengths.iter() {
+            assert!(length >= 2 && length < SIZE);
+        }
+
+        let mut fdn = Self {
+            lines: core::array::from_fn(|_| DelayLine::new()),
+            lengths,
+            filters: [DecayFilter::default(); LINES],
+            phases: core::array::from_fn(|i| i as f32 / LINES as f32),
+
+            matrix: FdnMatrix::Householder,
+            sample_rate,
+            rt60: (0., 0.),
+            modulation_depth: 0.,
+            modulation_rate: 0.,
+            output_gain: 1. / libm::sqrtf(LINES as f32 / 2.),
+            wet: 1.,
+            dry: 0.,
+        };
+
+        fdn.set_rt60(2., 1.);
+
+        fdn
+    }
+
+    pub fn matrix(&self) -> FdnMatrix {
+        self.matrix
+    }
+
+    pub fn set_matrix(&mut self, matrix: FdnMatrix) {
+        if matrix == FdnMatrix::Hadamard {
+            assert!(LINES.is_power_of_two());
+        }
+
+        self.matrix = matrix;
+    }
+
+    pub fn rt60(&self) -> (f32, f32) {
+        self.rt60
+    }
+
+    /// Set the time in seconds it takes to decay by 60dB at DC (`low`) and at
+    /// Nyquist (`high`), with a smooth transition in between.
+    pub fn set_rt60(&mut self, low: f32, high: f32) {
+        self.rt60 = (low, high);
+
+        for (filter, &length) in self.filters.iter_mut().zip(self.lengths.iter()) {
+            filter.set_gains(
+                decay_gain(length, low, self.sample_rate),
+                decay_gain(length, high, self.sample_rate),
+            );
+        }
+    }
+
+    /// Sweep every delay length by up to `depth` samples at roughly `rate` Hz
+    /// to break up ringing. Each line runs at a slightly different rate and
+    /// phase.
+    pub fn set_modulation(&mut self, depth: f32, rate: f32) {
+        for &length in self.lengths.iter() {
+            assert!(depth >= 0. && depth < length as f32 - 1.);
+            assert!(length as f32 + depth < SIZE as f32 - 1.);
+        }
+
+        self.modulation_depth = depth;
+        self.modulation_rate = rate;
+    }
+
+    pub fn wet(&self) -> f32 {
+        self.wet
+    }
+
+    pub fn set_wet(&mut self, value: f32) {
+        self.wet = value;
+    }
+
+    pub fn dry(&self) -> f32 {
+        self.dry
+    }
+
+    pub fn set_dry(&mut self, value: f32) {
+        self.dry = value;
+    }
+
+    fn mix(&self, values: &mut [f32; LINES]) {
+        match self.matrix {
+            FdnMatrix::Householder => {
+                let sum: f32 = values.iter().sum();
+                let reflection = sum * 2. / LINES as f32;
+
+                for value in values.iter_mut() {
+                    *value -= reflection;
+                }
+            }
+            FdnMatrix::Hadamard => {
+                let mut size = 1;
+                while size < LINES {
+                    for start in (0..LINES).step_by(size * 2) {
+                        for i in start..start + size {
+                            let (a, b) = (values[i], values[i + size]);
+                            values[i] = a + b;
+                            values[i + size] = a - b;
+                        }
+                    }
+                    size *= 2;
+                }
+
+                let scale = 1. / libm::sqrtf(LINES as f32);
+                for value in values.iter_mut() {
+                    *value *= scale;
+                }
+            }
+        }
+    }
+}
+
+// The gain that a single pass through `length` samples needs for the whole
+// network to decay by 60dB in `rt60` seconds.
+fn decay_gain(length: usize, rt60: f32, sample_rate: f32) -> f32 {
+    libm::powf(10., -3. * length as f32 / (rt60 * sample_rate))
+}
+
+impl<const LINES: usize, const SIZE: usize> Node<(f32, f32), (f32, f32)> for Fdn<LINES, SIZE> {
+    #[inline]
+    fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        let mut values = [0.; LINES];
+        let mut output = (0., 0.);
+
+        let increment = self.modulation_rate / self.sample_rate;
+
+        for (i, value) in values.iter_mut().enumerate() {
+            let mut delay = (self.lengths[i] - 1) as f32;
+
+            if self.modulation_depth > 0. {
+                delay += self.modulation_depth * libm::sinf(2. * PI * self.phases[i]);
+
+                // Spread the rates so the lines don't move together
+                self.phases[i] += increment * (1. + 0.1 * i as f32 / LINES as f32);
+                if self.phases[i] >= 1. {
+                    self.phases[i] -= 1.;
+                }
+            }
+
+            *value = self.filters[i].process(self.lines[i].tap_linear(delay));
+
+            if i % 2 == 0 {
+                output.0 += *value;
+            } else {
+                output.1 += *value;
+            }
+        }
+
+        self.mix(&mut values);
+
+        for (i, (line, value)) in self.lines.iter_mut().zip(values.iter()).enumerate() {
+            let input = if i % 2 == 0 { input.0 } else { input.1 };
+            line.process(value + input);
+        }
+
+        let wet = self.wet * self.output_gain;
+
+        (
+            output.0 * wet + input.0 * self.dry,
+            output.1 * wet + input.1 * self.dry,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 16000.;
+    const LENGTHS: [usize; 8] = [521, 581, 677, 743, 821, 877, 947, 1049];
+
+    // Fits a line to the energy envelope of the impulse response, in dB, and
+    // returns how long it takes to fall by 60dB.
+    fn measure_rt60(fdn: &mut Fdn<8, 1100>) -> f32 {
+        const WINDOW: usize = 400;
+        const WINDOWS: usize = 20;
+
+        let mut energies = [0.; WINDOWS];
+
+        for i in 0..WINDOW * (WINDOWS + 2) {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            let (left, right) = fdn.process(input);
+
+            // Skip the build up of echo density at the start
+            if i >= WINDOW * 2 {
+                energies[i / WINDOW - 2] += left * left + right * right;
+            }
+        }
+
+        let points = energies
+            .iter()
+            .enumerate()
+            .map(|(i, energy)| (i as f32 * WINDOW as f32, 10. * libm::log10f(*energy)));
+
+        let n = WINDOWS as f32;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0., 0., 0., 0.);
+        for (x, y) in points {
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+
+        let slope = (n * sxy - sx * sy) / (n * sxx - sx * sx);
+
+        -60. / slope / SAMPLE_RATE
+    }
+
+    #[test]
+    fn test_rt60_householder() {
+        let mut fdn: Fdn<8, 1100> = Fdn::new(SAMPLE_RATE, LENGTHS);
+        fdn.set_rt60(0.5, 0.5);
+
+        let rt60 = measure_rt60(&mut fdn);
+        assert!((rt60 - 0.5).abs() < 0.05, "{}", rt60);
+    }
+
+    #[test]
+    fn test_rt60_hadamard_modulated() {
+        let mut fdn: Fdn<8, 1100> = Fdn::new(SAMPLE_RATE, LENGTHS);
+        fdn.set_matrix(FdnMatrix::Hadamard);
+        fdn.set_modulation(8., 0.7);
+        fdn.set_rt60(0.3, 0.3);
+
+        let rt60 = measure_rt60(&mut fdn);
+        assert!((rt60 - 0.3).abs() < 0.03, "{}", rt60);
+    }
+
+    #[test]
+    fn test_decay_filter_gains() {
+        let mut filter = DecayFilter::default();
+        filter.set_gains(0.9, 0.5);
+
+        assert!((filter.b / (1. - filter.a) - 0.9).abs() < 1e-6);
+        assert!((filter.b / (1. + filter.a) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::math;
+use core::ops::{Add, AddAssign, Mul, Sub};
+
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl AddAssign for Complex {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Mul<f32> for Complex {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: f32) -> Self {
+        Self::new(self.re * other, self.im * other)
+    }
+}
+
+/// An FFT of size `N`, which must be a power of two, using the split-radix
+/// algorithm. The twiddle factors are computed up front, in a `const` context
+/// if needed, and nothing is allocated.
+pub struct Fft<const N: usize> {
+    twiddles: [Complex; N],
+}
+
+impl<const N: usize> Fft<N> {
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two());
+
+        let mut twiddles = [Complex::new(0., 0.); N];
+
+        let mut i = 0;
+        while i < N {
+            let angle = -2. * core::f64::consts::PI * i as f64 / N as f64;
+            twiddles[i] = Complex::new(math::cos(angle) as f32, math::sin(angle) as f32);
+            i += 1;
+        }
+
+        Self { twiddles }
+    }
+
+    pub fn forward(&self, buffer: &mut [Complex; N]) {
+        let input = *buffer;
+        self.split_radix(&input, 0, 1, buffer);
+    }
+
+    /// The inverse transform, scaled by `1 / N` so that it undoes `forward`.
+    pub fn inverse(&self, buffer: &mut [Complex; N]) {
+        let mut input = *buffer;
+        for value in input.iter_mut() {
+            *value = value.conj();
+        }
+
+        self.split_radix(&input, 0, 1, buffer);
+
+        let scale = 1. / N as f32;
+        for value in buffer.iter_mut() {
+            *value = value.conj() * scale;
+        }
+    }
+
+    /// Transform a real signal, using a complex transform of half the size.
+    /// The whole spectrum is written, although only the first `N / 2 + 1` bins
+    /// are unique.
+    pub fn forward_real(&self, input: &[f32; N], output: &mut [Complex; N]) {
+        if N == 1 {
+            output[0] = Complex::new(input[0], 0.);
+            return;
+        }
+
+        let half = N / 2;
+
+        // Pack even samples into the real part and odd into the imaginary
+        let mut packed = [Complex::default(); N];
+        for (value, pair) in packed.iter_mut().zip(input.chunks(2)) {
+            *value = Complex::new(pair[0], pair[1]);
+        }
+
+        self.split_radix(&packed, 0, 1, &mut output[..half]);
+        packed[..half].copy_from_slice(&output[..half]);
+
+        // Separate the spectra of the even and odd samples and combine them
+        for k in 0..=half {
+            let z = packed[k % half];
+            let mirror = packed[(half - k) % half].conj();
+
+            let even = (z + mirror) * 0.5;
+            let odd = (z - mirror) * 0.5;
+            // odd / i
+            let odd = Complex::new(odd.im, -odd.re);
+
+            output[k] = even + self.twiddles[k] * odd;
+        }
+
+        for k in half + 1..N {
+            output[k] = output[N - k].conj();
+        }
+    }
+
+    /// The inverse of `forward_real`, assuming `input` is the spectrum of a
+    /// real signal. Only the first `N / 2 + 1` bins are read.
+    pub fn inverse_real(&self, input: &[Complex; N], output: &mut [f32; N]) {
+        if N == 1 {
+            output[0] = input[0].re;
+            return;
+        }
+
+        let half = N / 2;
+
+        let mut packed = [Complex::default(); N];
+        for (k, value) in packed[..half].iter_mut().enumerate() {
+            let x = input[k];
+            let mirror = input[half - k].conj();
+
+            let even = (x + mirror) * 0.5;
+            let odd = (x - mirror) * 0.5 * self.twiddles[k].conj();
+
+            // even + i * odd, conjugated for the inverse
+            *value = Complex::new(even.re - odd.im, even.im + odd.re).conj();
+        }
+
+        let mut result = [Complex::default(); N];
+        self.split_radix(&packed, 0, 1, &mut result[..half]);
+
+        let scale = 1. / half as f32;
+        for (pair, value) in output.chunks_mut(2).zip(result.iter()) {
+            let value = value.conj() * scale;
+            pair[0] = value.re;
+            pair[1] = value.im;
+        }
+    }
+
+    // Out of place recursive split-radix decimation in time. Reads
+    // `output.len()` samples from `input`, starting at `offset` and spaced by
+    // `stride`.
+    fn split_radix(
+        &self,
+        input: &[Complex; N],
+        offset: usize,
+        stride: usize,
+        output: &mut [Complex],
+    ) {
+        let n = output.len();
+
+        match n {
+            1 => {
+                output[0] = input[offset];
+                return;
+            }
+            2 => {
+                let a = input[offset];
+                let b = input[offset + stride];
+                output[0] = a + b;
+                output[1] = a - b;
+                return;
+            }
+            _ => {}
+        }
+
+        let quarter = n / 4;
+
+        {
+            let (even, odd) = output.split_at_mut(n / 2);
+            let (odd_1, odd_3) = odd.split_at_mut(quarter);
+
+            self.split_radix(input, offset, stride * 2, even);
+            self.split_radix(input, offset + stride, stride * 4, odd_1);
+            self.split_radix(input, offset + stride * 3, stride * 4, odd_3);
+        }
+
+        let step = N / n;
+
+        for k in 0..quarter {
+            let a = output[k + quarter * 2] * self.twiddles[k * step];
+            let b = output[k + quarter * 3] * self.twiddles[k * step * 3];
+
+            let sum = a + b;
+            let difference = a - b;
+            // -i * difference
+            let rotated = Complex::new(difference.im, -difference.re);
+
+            let u1 = output[k];
+            let u2 = output[k + quarter];
+
+            output[k] = u1 + sum;
+            output[k + quarter * 2] = u1 - sum;
+            output[k + quarter] = u2 + rotated;
+            output[k + quarter * 3] = u2 - rotated;
+        }
+    }
+}
+
+impl<const N: usize> Default for Fft<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FFT: Fft<64> = Fft::new();
+
+    fn signal(i: usize) -> f32 {
+        libm::sinf(i as f32 * 0.7) + (i % 3) as f32 - 1.
+    }
+
+    fn dft<const N: usize>(input: &[Complex; N]) -> [Complex; N] {
+        let mut output = [Complex::default(); N];
+
+        for (k, bin) in output.iter_mut().enumerate() {
+            for (n, x) in input.iter().enumerate() {
+                let angle = -2. * core::f32::consts::PI * ((k * n) % N) as f32 / N as f32;
+                *bin += *x * Complex::new(libm::cosf(angle), libm::sinf(angle));
+            }
+        }
+
+        output
+    }
+
+    fn check_size<const N: usize>() {
+        let fft: Fft<N> = Fft::new();
+
+        let mut buffer = [Complex::default(); N];
+        for (i, value) in buffer.iter_mut().enumerate() {
+            *value = Complex::new(signal(i), signal(i + 100));
+        }
+
+        let input = buffer;
+        fft.forward(&mut buffer);
+
+        for (value, expected) in buffer.iter().zip(dft(&input).iter()) {
+            assert!((*value - *expected).norm_sqr() < 1e-7);
+        }
+
+        fft.inverse(&mut buffer);
+        for (value, x) in buffer.iter().zip(input.iter()) {
+            assert!((*value - *x).norm_sqr() < 1e-10);
+        }
+
+        let mut real = [0.; N];
+        for (i, value) in real.iter_mut().enumerate() {
+            *value = signal(i);
+        }
+
+        let mut spectrum = [Complex::default(); N];
+        fft.forward_real(&real, &mut spectrum);
+
+        let mut complex = [Complex::default(); N];
+        for (value, x) in complex.iter_mut().zip(real.iter()) {
+            value.re = *x;
+        }
+
+        for (value, expected) in spectrum.iter().zip(dft(&complex).iter()) {
+            assert!((*value - *expected).norm_sqr() < 1e-7);
+        }
+
+        let mut output = [0.; N];
+        fft.inverse_real(&spectrum, &mut output);
+        for (value, x) in output.iter().zip(real.iter()) {
+            assert!((value - x).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_matches_dft() {
+        check_size::<1>();
+        check_size::<2>();
+        check_size::<4>();
+        check_size::<8>();
+        check_size::<16>();
+        check_size::<32>();
+        check_size::<128>();
+    }
+
+    #[test]
+    fn test_parseval() {
+        let mut buffer = [Complex::default(); 64];
+        let mut energy = 0.;
+        for (i, value) in buffer.iter_mut().enumerate() {
+            *value = Complex::new(signal(i), signal(i * 7));
+            energy += value.norm_sqr();
+        }
+
+        FFT.forward(&mut buffer);
+
+        let spectral_energy: f32 = buffer.iter().map(|bin| bin.norm_sqr()).sum::<f32>() / 64.;
+        assert!((energy - spectral_energy).abs() < 1e-3 * energy);
+
+        let mut real = [0.; 64];
+        let mut energy = 0.;
+        for (i, value) in real.iter_mut().enumerate() {
+            *value = signal(i * 3);
+            energy += *value * *value;
+        }
+
+        FFT.forward_real(&real, &mut buffer);
+
+        let spectral_energy: f32 = buffer.iter().map(|bin| bin.norm_sqr()).sum::<f32>() / 64.;
+        assert!((energy - spectral_energy).abs() < 1e-3 * energy);
+    }
+}
@@ -0,0 +1,668 @@
+use super::{Node, Window};
+use crate::math;
+use core::f64::consts::PI;
+use core::fmt;
+use libm;
+
+/// The response of a windowed-sinc filter. Frequencies are relative to the
+/// sample rate, so must lie within (0, 0.5), with the lower frequency of a
+/// band first.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FirType {
+    Lowpass(f32),
+    /// Needs an odd number of taps.
+    Highpass(f32),
+    /// Passes between the lower and upper frequencies.
+    Bandpass(f32, f32),
+    /// Rejects between the lower and upper frequencies. Needs an odd number
+    /// of taps.
+    Bandstop(f32, f32),
+}
+
+/// A band of an equiripple design, from `start` to `end` relative to the
+/// sample rate. The error within the band is scaled by `weight`, so a larger
+/// weight gives less ripple there.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RemezBand {
+    pub start: f32,
+    pub end: f32,
+    pub gain: f32,
+    pub weight: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FirError {
+    /// Equiripple designs need an odd number of taps, and at least as many
+    /// as to leave room for two extremal frequencies per band.
+    InvalidTaps,
+    /// Bands must be in order, must not overlap, must lie within [0, 0.5]
+    /// and must have a positive weight.
+    InvalidBands,
+    /// The exchange algorithm didn't settle on an equiripple solution.
+    NoConvergence,
+}
+
+impl fmt::Display for FirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FirError::*;
+
+        let message = match self {
+            InvalidTaps => "number of taps must be odd and large enough for the bands",
+            InvalidBands => "bands must be ordered, disjoint and within [0, 0.5]",
+            NoConvergence => "remez exchange failed to converge",
+        };
+
+        f.write_str(message)
+    }
+}
+
+// How many grid points the Remez exchange searches per extremal frequency.
+const GRID_DENSITY: usize = 16;
+const MAX_ITERATIONS: usize = 100;
+
+/// A finite impulse response filter with `TAPS` taps, using a circular
+/// buffer of past input.
+pub struct Fir<const TAPS: usize> {
+    taps: [f32; TAPS],
+    buffer: [f32; TAPS],
+    position: usize,
+}
+
+impl<const TAPS: usize> Fir<TAPS> {
+    pub const fn new(taps: [f32; TAPS]) -> Self {
+        assert!(TAPS > 0);
+
+        Self {
+            taps,
+            buffer: [0.; TAPS],
+            position: 0,
+        }
+    }
+
+    /// A linear phase filter designed by windowing an ideal impulse response.
+    /// Lowpass filters have unity gain at DC, highpass at Nyquist.
+    pub const fn windowed_sinc(fir_type: FirType, window: Window) -> Self {
+        let window = window.symmetric::<TAPS>();
+
+        let taps = match fir_type {
+            FirType::Lowpass(cutoff) => {
+                assert!(cutoff > 0. && cutoff < 0.5);
+                sinc_lowpass(cutoff, &window)
+            }
+            FirType::Highpass(cutoff) => {
+                assert!(cutoff > 0. && cutoff < 0.5);
+                assert!(TAPS % 2 == 1);
+                invert(sinc_lowpass(cutoff, &window))
+            }
+            FirType::Bandpass(low, high) => {
+                assert!(low > 0. && low < high && high < 0.5);
+                subtract(sinc_lowpass(high, &window), sinc_lowpass(low, &window))
+            }
+            FirType::Bandstop(low, high) => {
+                assert!(low > 0. && low < high && high < 0.5);
+                assert!(TAPS % 2 == 1);
+                invert(subtract(
+                    sinc_lowpass(high, &window),
+                    sinc_lowpass(low, &window),
+                ))
+            }
+        };
+
+        let mut result = [0.; TAPS];
+        let mut i = 0;
+        while i < TAPS {
+            result[i] = taps[i] as f32;
+            i += 1;
+        }
+
+        Self::new(result)
+    }
+
+    /// An optimal linear phase filter that minimises the largest weighted
+    /// error across `bands`, using the Parks-McClellan algorithm. Frequencies
+    /// outside the bands are left unconstrained.
+    pub fn remez(bands: &[RemezBand]) -> Result<Self, FirError> {
+        remez(bands).map(Self::new)
+    }
+
+    pub fn taps(&self) -> &[f32; TAPS] {
+        &self.taps
+    }
+
+    /// Replace the taps while keeping the input history.
+    pub fn set_taps(&mut self, taps: [f32; TAPS]) {
+        self.taps = taps;
+    }
+
+    /// The group delay in samples, assuming symmetric (linear phase) taps.
+    pub fn latency(&self) -> f32 {
+        (TAPS - 1) as f32 / 2.
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer = [0.; TAPS];
+        self.position = 0;
+    }
+}
+
+impl<const TAPS: usize> Node<f32, f32> for Fir<TAPS> {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let position = self.position;
+        self.buffer[position] = input;
+
+        // Walk backwards through the buffer in two contiguous runs rather
+        // than wrapping every index
+        let (older, newer) = self.buffer.split_at(position + 1);
+        let (recent_taps, old_taps) = self.taps.split_at(position + 1);
+
+        let mut output = 0.;
+        for (tap, sample) in recent_taps.iter().zip(older.iter().rev()) {
+            output += tap * sample;
+        }
+        for (tap, sample) in old_taps.iter().zip(newer.iter().rev()) {
+            output += tap * sample;
+        }
+
+        self.position = if position + 1 == TAPS {
+            0
+        } else {
+            position + 1
+        };
+
+        output
+    }
+}
+
+// A windowed ideal lowpass, normalised to unity gain at DC.
+const fn sinc_lowpass<const TAPS: usize>(cutoff: f32, window: &[f32; TAPS]) -> [f64; TAPS] {
+    let cutoff = cutoff as f64;
+    let centre = (TAPS - 1) as f64 / 2.;
+
+    let mut taps = [0.; TAPS];
+    let mut sum = 0.;
+
+    let mut i = 0;
+    while i < TAPS {
+        let t = i as f64 - centre;
+        let ideal = if t == 0. {
+            2. * cutoff
+        } else {
+            math::sin(2. * PI * cutoff * t) / (PI * t)
+        };
+
+        taps[i] = ideal * window[i] as f64;
+        sum += taps[i];
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < TAPS {
+        taps[i] /= sum;
+        i += 1;
+    }
+
+    taps
+}
+
+// Subtracts the response from an impulse at the centre tap.
+const fn invert<const TAPS: usize>(mut taps: [f64; TAPS]) -> [f64; TAPS] {
+    let mut i = 0;
+    while i < TAPS {
+        taps[i] = -taps[i];
+        i += 1;
+    }
+    taps[TAPS / 2] += 1.;
+
+    taps
+}
+
+const fn subtract<const TAPS: usize>(mut a: [f64; TAPS], b: [f64; TAPS]) -> [f64; TAPS] {
+    let mut i = 0;
+    while i < TAPS {
+        a[i] -= b[i];
+        i += 1;
+    }
+
+    a
+}
+
+// The points searched for extremal frequencies, evenly spaced by about
+// `spacing` within each band and always including the band edges. Yields
+// (frequency, desired gain, weight).
+fn grid(bands: &[RemezBand], spacing: f64) -> impl Iterator<Item = (f64, f64, f64)> + '_ {
+    bands.iter().flat_map(move |band| {
+        let start = band.start as f64;
+        let width = band.end as f64 - start;
+        let steps = libm::ceil(width / spacing).max(1.) as usize;
+
+        (0..=steps).map(move |i| {
+            (
+                start + width * i as f64 / steps as f64,
+                band.gain as f64,
+                band.weight as f64,
+            )
+        })
+    })
+}
+
+// Barycentric Lagrange interpolation through `xs` and `ys` with precomputed
+// `weights`.
+fn interpolate(x: f64, xs: &[f64], ys: &[f64], weights: &[f64]) -> f64 {
+    let mut numerator = 0.;
+    let mut denominator = 0.;
+
+    for ((&xk, &yk), &wk) in xs.iter().zip(ys.iter()).zip(weights.iter()) {
+        let difference = x - xk;
+        if difference == 0. {
+            return yk;
+        }
+
+        let term = wk / difference;
+        numerator += term * yk;
+        denominator += term;
+    }
+
+    numerator / denominator
+}
+
+fn barycentric_weights(xs: &[f64], weights: &mut [f64]) {
+    for (k, weight) in weights.iter_mut().enumerate() {
+        let mut product = 1.;
+        for (j, &xj) in xs.iter().enumerate() {
+            if j != k {
+                // Scaled to avoid overflow with many points
+                product *= 2. * (xs[k] - xj);
+            }
+        }
+        *weight = 1. / product;
+    }
+}
+
+fn remez<const TAPS: usize>(bands: &[RemezBand]) -> Result<[f32; TAPS], FirError> {
+    if TAPS.is_multiple_of(2) {
+        return Err(FirError::InvalidTaps);
+    }
+
+    let mut previous_end = 0.;
+    for (i, band) in bands.iter().enumerate() {
+        let ordered = if i == 0 {
+            band.start >= 0.
+        } else {
+            band.start > previous_end
+        };
+
+        let valid = ordered
+            && band.end > band.start
+            && band.end <= 0.5
+            && band.weight > 0.
+            && band.gain.is_finite();
+
+        if !valid {
+            return Err(FirError::InvalidBands);
+        }
+
+        previous_end = band.end;
+    }
+
+    // A type I filter's response is a cosine polynomial of this order, which
+    // the alternation theorem says will touch the error bound at `order + 2`
+    // frequencies. Spare room is kept for extra extrema found at band edges.
+    let order = (TAPS - 1) / 2;
+    let extremal_count = order + 2;
+    if bands.is_empty() || extremal_count + 2 * bands.len() > TAPS {
+        return Err(FirError::InvalidTaps);
+    }
+
+    let spacing = 0.5 / (GRID_DENSITY * (order + 1)) as f64;
+    let grid_length = grid(bands, spacing).count();
+    if grid_length < extremal_count {
+        return Err(FirError::InvalidTaps);
+    }
+
+    // Start with extremal frequencies spread evenly across the grid
+    let mut frequencies = [0.; TAPS];
+    let mut errors = [0f64; TAPS];
+    let mut next = 0;
+    for (index, (frequency, _, _)) in grid(bands, spacing).enumerate() {
+        if next < extremal_count && index == next * (grid_length - 1) / (extremal_count - 1) {
+            frequencies[next] = frequency;
+            next += 1;
+        }
+    }
+
+    let mut xs = [0.; TAPS];
+    let mut ys = [0.; TAPS];
+    let mut weights = [0.; TAPS];
+
+    for _ in 0..MAX_ITERATIONS {
+        let count = extremal_count;
+
+        for (x, &frequency) in xs[..count].iter_mut().zip(frequencies.iter()) {
+            *x = libm::cos(2. * PI * frequency);
+        }
+
+        // The deviation that the response alternates about at the extremals
+        barycentric_weights(&xs[..count], &mut weights[..count]);
+        let mut numerator = 0.;
+        let mut denominator = 0.;
+        let mut sign = 1.;
+        for (k, &frequency) in frequencies[..count].iter().enumerate() {
+            let (desired, weight) = band_at(bands, frequency);
+            numerator += weights[k] * desired;
+            denominator += sign * weights[k] / weight;
+            sign = -sign;
+        }
+        let deviation = numerator / denominator;
+
+        // Interpolate the response through all but the last extremal
+        let mut sign = 1.;
+        for (y, &frequency) in ys[..count - 1].iter_mut().zip(frequencies.iter()) {
+            let (desired, weight) = band_at(bands, frequency);
+            *y = desired - sign * deviation / weight;
+            sign = -sign;
+        }
+        barycentric_weights(&xs[..count - 1], &mut weights[..count - 1]);
+
+        let response = |frequency: f64| {
+            interpolate(
+                libm::cos(2. * PI * frequency),
+                &xs[..count - 1],
+                &ys[..count - 1],
+                &weights[..count - 1],
+            )
+        };
+
+        // Collect the alternating local extrema of the weighted error, keeping
+        // the largest of any run with the same sign
+        let mut found = 0;
+        let mut largest: f64 = 0.;
+        let mut previous = 0.;
+        let mut points = grid(bands, spacing).peekable();
+        let mut band_start = true;
+
+        while let Some((frequency, desired, weight)) = points.next() {
+            let error = weight * (desired - response(frequency));
+            let band_end = match points.peek() {
+                Some(&(next, _, _)) => !in_same_band(bands, frequency, next),
+                None => true,
+            };
+
+            largest = largest.max(error.abs());
+
+            let next_error = if band_end {
+                0.
+            } else {
+                let (next, desired, weight) = *points.peek().unwrap();
+                weight * (desired - response(next))
+            };
+            // A point is extremal if it's a local peak of |E|. Band edges have
+            // no neighbour outside the band, and neighbours of the opposite
+            // sign don't count so the last point before a zero crossing is a
+            // peak.
+            let sign = if error < 0. { -1. } else { 1. };
+            let is_peak = (band_start || sign * error >= sign * previous)
+                && (band_end || sign * error >= sign * next_error);
+
+            if is_peak && error.abs() >= deviation.abs() * (1. - 1e-6) {
+                if found > 0 && (errors[found - 1] > 0.) == (error > 0.) {
+                    if error.abs() > errors[found - 1].abs() {
+                        frequencies[found - 1] = frequency;
+                        errors[found - 1] = error;
+                    }
+                } else {
+                    if found == TAPS {
+                        frequencies.copy_within(1.., 0);
+                        errors.copy_within(1.., 0);
+                        found -= 1;
+                    }
+
+                    frequencies[found] = frequency;
+                    errors[found] = error;
+                    found += 1;
+                }
+            }
+
+            previous = error;
+            band_start = band_end;
+        }
+
+        if found < extremal_count {
+            return Err(FirError::NoConvergence);
+        }
+
+        // Drop the smaller end until only the needed number remain
+        let mut first = 0;
+        while found - first > extremal_count {
+            if errors[first].abs() < errors[found - 1].abs() {
+                first += 1;
+            } else {
+                found -= 1;
+            }
+        }
+        frequencies.copy_within(first..found, 0);
+        errors.copy_within(first..found, 0);
+
+        let converged = largest - deviation.abs() <= 1e-4 * deviation.abs();
+        if converged {
+            // Sample the response at evenly spaced frequencies and take the
+            // inverse DFT of the symmetric spectrum
+            let mut samples = [0.; TAPS];
+            for (k, sample) in samples[..=order].iter_mut().enumerate() {
+                *sample = response(k as f64 / TAPS as f64);
+            }
+
+            let mut taps = [0.; TAPS];
+            for (n, tap) in taps.iter_mut().enumerate() {
+                let t = n as f64 - order as f64;
+                let mut sum = samples[0];
+                for (k, &sample) in samples[1..=order].iter().enumerate() {
+                    sum += 2. * sample * libm::cos(2. * PI * (k + 1) as f64 * t / TAPS as f64);
+                }
+                *tap = (sum / TAPS as f64) as f32;
+            }
+
+            return Ok(taps);
+        }
+    }
+
+    Err(FirError::NoConvergence)
+}
+
+fn band_at(bands: &[RemezBand], frequency: f64) -> (f64, f64) {
+    for band in bands.iter() {
+        if frequency <= band.end as f64 {
+            return (band.gain as f64, band.weight as f64);
+        }
+    }
+
+    let last = bands[bands.len() - 1];
+    (last.gain as f64, last.weight as f64)
+}
+
+fn in_same_band(bands: &[RemezBand], a: f64, b: f64) -> bool {
+    bands
+        .iter()
+        .any(|band| a >= band.start as f64 && b <= band.end as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The magnitude response of linear phase taps at `frequency`.
+    fn magnitude(taps: &[f32], frequency: f32) -> f32 {
+        let (mut re, mut im) = (0., 0.);
+        for (n, tap) in taps.iter().enumerate() {
+            let angle = 2. * core::f32::consts::PI * frequency * n as f32;
+            re += tap * libm::cosf(angle);
+            im -= tap * libm::sinf(angle);
+        }
+        libm::hypotf(re, im)
+    }
+
+    fn band_limits(taps: &[f32], start: f32, end: f32) -> (f32, f32) {
+        let mut limits = (f32::MAX, 0f32);
+        for i in 0..=200 {
+            let gain = magnitude(taps, start + (end - start) * i as f32 / 200.);
+            limits = (limits.0.min(gain), limits.1.max(gain));
+        }
+        limits
+    }
+
+    #[test]
+    fn test_process_matches_convolution() {
+        let taps = [0.5, -0.25, 1., 0.125, 2.];
+        let mut fir = Fir::new(taps);
+
+        let input: [f32; 12] = [1., 0., 0., 2., -1., 3., 0.5, 0., 0., 0., 0., 0.];
+        for (n, sample) in input.iter().enumerate() {
+            let expected: f32 = (0..=n.min(4)).map(|k| taps[k] * input[n - k]).sum();
+            assert_eq!(fir.process(*sample), expected);
+        }
+
+        fir.reset();
+        for (i, tap) in taps.iter().enumerate() {
+            let input = if i == 0 { 1. } else { 0. };
+            assert_eq!(fir.process(input), *tap);
+        }
+    }
+
+    #[test]
+    fn test_windowed_sinc() {
+        const LOWPASS: Fir<101> = Fir::windowed_sinc(FirType::Lowpass(0.1), Window::Blackman);
+        let taps = LOWPASS.taps();
+
+        assert!((magnitude(taps, 0.) - 1.).abs() < 1e-5);
+        assert!(band_limits(taps, 0., 0.07).0 > 0.999);
+        assert!(band_limits(taps, 0.13, 0.5).1 < 1e-3);
+        assert_eq!(LOWPASS.latency(), 50.);
+
+        for (tap, mirrored) in taps.iter().zip(taps.iter().rev()) {
+            assert_eq!(tap, mirrored);
+        }
+
+        let highpass: Fir<101> = Fir::windowed_sinc(FirType::Highpass(0.2), Window::Kaiser(8.));
+        assert!(band_limits(highpass.taps(), 0., 0.165).1 < 1e-3);
+        assert!((band_limits(highpass.taps(), 0.235, 0.5).0 - 1.).abs() < 1e-3);
+
+        let bandpass: Fir<101> = Fir::windowed_sinc(FirType::Bandpass(0.1, 0.3), Window::Blackman);
+        assert!(band_limits(bandpass.taps(), 0., 0.07).1 < 1e-3);
+        assert!(band_limits(bandpass.taps(), 0.13, 0.27).0 > 0.998);
+        assert!(band_limits(bandpass.taps(), 0.33, 0.5).1 < 1e-3);
+
+        let bandstop: Fir<101> = Fir::windowed_sinc(FirType::Bandstop(0.1, 0.3), Window::Blackman);
+        assert!(band_limits(bandstop.taps(), 0., 0.07).0 > 0.998);
+        assert!(band_limits(bandstop.taps(), 0.13, 0.27).1 < 1e-3);
+        assert!(band_limits(bandstop.taps(), 0.33, 0.5).0 > 0.998);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_windowed_sinc_above_nyquist() {
+        let _: Fir<31> = Fir::windowed_sinc(FirType::Lowpass(0.7), Window::Blackman);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_windowed_sinc_reversed_band() {
+        let _: Fir<31> = Fir::windowed_sinc(FirType::Bandpass(0.3, 0.1), Window::Blackman);
+    }
+
+    #[test]
+    fn test_remez_lowpass_is_equiripple() {
+        let bands = [
+            RemezBand {
+                start: 0.,
+                end: 0.1,
+                gain: 1.,
+                weight: 1.,
+            },
+            RemezBand {
+                start: 0.15,
+                end: 0.5,
+                gain: 0.,
+                weight: 1.,
+            },
+        ];
+
+        let fir: Fir<63> = Fir::remez(&bands).unwrap();
+        let taps = fir.taps();
+
+        for (tap, mirrored) in taps.iter().zip(taps.iter().rev()) {
+            assert!((tap - mirrored).abs() < 1e-6);
+        }
+
+        let (low, high) = band_limits(taps, 0., 0.1);
+        let passband_ripple = (1. - low).max(high - 1.);
+        let stopband_ripple = band_limits(taps, 0.15, 0.5).1;
+
+        // Equal weights give equal ripple in both bands
+        assert!(stopband_ripple < 3e-3, "{}", stopband_ripple);
+        assert!(
+            (passband_ripple - stopband_ripple).abs() < 0.1 * stopband_ripple,
+            "{} {}",
+            passband_ripple,
+            stopband_ripple
+        );
+
+        // Weighting the stopband trades passband ripple for attenuation, in
+        // proportion to the weight
+        let mut weighted = bands;
+        weighted[1].weight = 10.;
+        let fir: Fir<63> = Fir::remez(&weighted).unwrap();
+
+        let (low, high) = band_limits(fir.taps(), 0., 0.1);
+        let weighted_passband_ripple = (1. - low).max(high - 1.);
+        let weighted_stopband_ripple = band_limits(fir.taps(), 0.15, 0.5).1;
+
+        assert!(weighted_stopband_ripple < stopband_ripple);
+        assert!(
+            (weighted_passband_ripple - 10. * weighted_stopband_ripple).abs()
+                < weighted_passband_ripple * 0.1,
+            "{} {}",
+            weighted_passband_ripple,
+            weighted_stopband_ripple
+        );
+    }
+
+    #[test]
+    fn test_remez_bandpass() {
+        let band = |start, end, gain| RemezBand {
+            start,
+            end,
+            gain,
+            weight: 1.,
+        };
+        let bands = [band(0., 0.1, 0.), band(0.15, 0.3, 1.), band(0.35, 0.5, 0.)];
+
+        let fir: Fir<81> = Fir::remez(&bands).unwrap();
+        assert!(band_limits(fir.taps(), 0., 0.1).1 < 1e-2);
+        assert!(band_limits(fir.taps(), 0.15, 0.3).0 > 0.99);
+        assert!(band_limits(fir.taps(), 0.35, 0.5).1 < 1e-2);
+    }
+
+    #[test]
+    fn test_remez_errors() {
+        let band = |start, end| RemezBand {
+            start,
+            end,
+            gain: 1.,
+            weight: 1.,
+        };
+
+        assert_eq!(
+            Fir::<32>::remez(&[band(0., 0.5)]).err(),
+            Some(FirError::InvalidTaps)
+        );
+        assert_eq!(Fir::<31>::remez(&[]).err(), Some(FirError::InvalidTaps));
+        assert_eq!(
+            Fir::<31>::remez(&[band(0., 0.2), band(0.1, 0.5)]).err(),
+            Some(FirError::InvalidBands)
+        );
+        assert_eq!(
+            Fir::<31>::remez(&[band(0., 0.6)]).err(),
+            Some(FirError::InvalidBands)
+        );
+    }
+}
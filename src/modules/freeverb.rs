@@ -8,339 +8,256 @@ const SCALE_DAMPENING: f32 = 0.4;
 const SCALE_ROOM: f32 = 0.28;
 const OFFSET_ROOM: f32 = 0.7;
 
-const STEREO_SPREAD: usize = 23;
-
-const COMB_TUNING_L1: usize = 1116;
-const COMB_TUNING_R1: usize = 1116 + STEREO_SPREAD;
-const COMB_TUNING_L2: usize = 1188;
-const COMB_TUNING_R2: usize = 1188 + STEREO_SPREAD;
-const COMB_TUNING_L3: usize = 1277;
-const COMB_TUNING_R3: usize = 1277 + STEREO_SPREAD;
-const COMB_TUNING_L4: usize = 1356;
-const COMB_TUNING_R4: usize = 1356 + STEREO_SPREAD;
-const COMB_TUNING_L5: usize = 1422;
-const COMB_TUNING_R5: usize = 1422 + STEREO_SPREAD;
-const COMB_TUNING_L6: usize = 1491;
-const COMB_TUNING_R6: usize = 1491 + STEREO_SPREAD;
-const COMB_TUNING_L7: usize = 1557;
-const COMB_TUNING_R7: usize = 1557 + STEREO_SPREAD;
-const COMB_TUNING_L8: usize = 1617;
-const COMB_TUNING_R8: usize = 1617 + STEREO_SPREAD;
-
-const ALLPASS_TUNING_L1: usize = 556;
-const ALLPASS_TUNING_R1: usize = 556 + STEREO_SPREAD;
-const ALLPASS_TUNING_L2: usize = 441;
-const ALLPASS_TUNING_R2: usize = 441 + STEREO_SPREAD;
-const ALLPASS_TUNING_L3: usize = 341;
-const ALLPASS_TUNING_R3: usize = 341 + STEREO_SPREAD;
-const ALLPASS_TUNING_L4: usize = 225;
-const ALLPASS_TUNING_R4: usize = 225 + STEREO_SPREAD;
+/// Scale a tuning given in samples at 44.1kHz to `sample_rate`, rounding to
+/// the nearest sample.
+pub const fn scale_tuning(samples: usize, sample_rate: usize) -> usize {
+    (samples * sample_rate + 22050) / 44100
+}
+
+/// The delay lengths, in samples, of each comb and allpass stage. The right
+/// channel uses the same lengths plus `stereo_spread`.
+#[derive(Copy, Clone)]
+pub struct FreeverbTuning<const COMBS: usize, const ALLPASSES: usize> {
+    pub combs: [usize; COMBS],
+    pub allpasses: [usize; ALLPASSES],
+    pub stereo_spread: usize,
+}
+
+impl<const COMBS: usize, const ALLPASSES: usize> FreeverbTuning<COMBS, ALLPASSES> {
+    /// Scale a tuning designed for 44.1kHz to `sample_rate`.
+    pub const fn scaled(&self, sample_rate: usize) -> Self {
+        let mut tuning = *self;
+
+        let mut i = 0;
+        while i < COMBS {
+            tuning.combs[i] = scale_tuning(self.combs[i], sample_rate);
+            i += 1;
+        }
+
+        let mut i = 0;
+        while i < ALLPASSES {
+            tuning.allpasses[i] = scale_tuning(self.allpasses[i], sample_rate);
+            i += 1;
+        }
+
+        tuning.stereo_spread = scale_tuning(self.stereo_spread, sample_rate);
+        tuning
+    }
 
+    /// The smallest `COMB_SIZE` a `Freeverb` needs to hold this tuning.
+    pub const fn comb_size(&self) -> usize {
+        max(&self.combs) + self.stereo_spread
+    }
+
+    /// The smallest `ALLPASS_SIZE` a `Freeverb` needs to hold this tuning.
+    pub const fn allpass_size(&self) -> usize {
+        max(&self.allpasses) + self.stereo_spread
+    }
+}
+
+const fn max<const N: usize>(values: &[usize; N]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < N {
+        if values[i] > max {
+            max = values[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+pub const TUNING_44100: FreeverbTuning<8, 4> = FreeverbTuning {
+    combs: [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617],
+    allpasses: [556, 441, 341, 225],
+    stereo_spread: 23,
+};
+
+pub const TUNING_48000: FreeverbTuning<8, 4> = TUNING_44100.scaled(48000);
+pub const TUNING_88200: FreeverbTuning<8, 4> = TUNING_44100.scaled(88200);
+pub const TUNING_96000: FreeverbTuning<8, 4> = TUNING_44100.scaled(96000);
+pub const TUNING_192000: FreeverbTuning<8, 4> = TUNING_44100.scaled(192000);
+
+/// A Freeverb with `COMBS` parallel combs followed by `ALLPASSES` series
+/// allpasses per channel. Every comb has room for `COMB_SIZE` samples and every
+/// allpass for `ALLPASS_SIZE`; the actual lengths come from a
+/// `FreeverbTuning`.
 pub struct Freeverb<
-    const COMB_TUNING_L1: usize,
-    const COMB_TUNING_R1: usize,
-    const COMB_TUNING_L2: usize,
-    const COMB_TUNING_R2: usize,
-    const COMB_TUNING_L3: usize,
-    const COMB_TUNING_R3: usize,
-    const COMB_TUNING_L4: usize,
-    const COMB_TUNING_R4: usize,
-    const COMB_TUNING_L5: usize,
-    const COMB_TUNING_R5: usize,
-    const COMB_TUNING_L6: usize,
-    const COMB_TUNING_R6: usize,
-    const COMB_TUNING_L7: usize,
-    const COMB_TUNING_R7: usize,
-    const COMB_TUNING_L8: usize,
-    const COMB_TUNING_R8: usize,
-    const ALLPASS_TUNING_L1: usize,
-    const ALLPASS_TUNING_R1: usize,
-    const ALLPASS_TUNING_L2: usize,
-    const ALLPASS_TUNING_R2: usize,
-    const ALLPASS_TUNING_L3: usize,
-    const ALLPASS_TUNING_R3: usize,
-    const ALLPASS_TUNING_L4: usize,
-    const ALLPASS_TUNING_R4: usize,
+    const COMBS: usize,
+    const ALLPASSES: usize,
+    const COMB_SIZE: usize,
+    const ALLPASS_SIZE: usize,
 > {
-    comb_1: (
-        LowpassFeedbackComb<COMB_TUNING_L1>,
-        LowpassFeedbackComb<COMB_TUNING_R1>,
-    ),
-    comb_2: (
-        LowpassFeedbackComb<COMB_TUNING_L2>,
-        LowpassFeedbackComb<COMB_TUNING_R2>,
-    ),
-    comb_3: (
-        LowpassFeedbackComb<COMB_TUNING_L3>,
-        LowpassFeedbackComb<COMB_TUNING_R3>,
-    ),
-    comb_4: (
-        LowpassFeedbackComb<COMB_TUNING_L4>,
-        LowpassFeedbackComb<COMB_TUNING_R4>,
-    ),
-    comb_5: (
-        LowpassFeedbackComb<COMB_TUNING_L5>,
-        LowpassFeedbackComb<COMB_TUNING_R5>,
-    ),
-    comb_6: (
-        LowpassFeedbackComb<COMB_TUNING_L6>,
-        LowpassFeedbackComb<COMB_TUNING_R6>,
-    ),
-    comb_7: (
-        LowpassFeedbackComb<COMB_TUNING_L7>,
-        LowpassFeedbackComb<COMB_TUNING_R7>,
-    ),
-    comb_8: (
-        LowpassFeedbackComb<COMB_TUNING_L8>,
-        LowpassFeedbackComb<COMB_TUNING_R8>,
-    ),
-
-    allpass_1: (AllPass<ALLPASS_TUNING_L1>, AllPass<ALLPASS_TUNING_R1>),
-    allpass_2: (AllPass<ALLPASS_TUNING_L2>, AllPass<ALLPASS_TUNING_R2>),
-    allpass_3: (AllPass<ALLPASS_TUNING_L3>, AllPass<ALLPASS_TUNING_R3>),
-    allpass_4: (AllPass<ALLPASS_TUNING_L4>, AllPass<ALLPASS_TUNING_R4>),
+    combs: [(
+        LowpassFeedbackComb<COMB_SIZE>,
+        LowpassFeedbackComb<COMB_SIZE>,
+    ); COMBS],
+    allpasses: [(AllPass<ALLPASS_SIZE>, AllPass<ALLPASS_SIZE>); ALLPASSES],
 
+    params: FreeverbParams,
     wet_gains: (f32, f32),
-    wet: f32,
-    width: f32,
-    dry: f32,
     input_gain: f32,
-    dampening: f32,
-    room_size: f32,
-    frozen: bool,
+}
+
+/// A snapshot of every user-facing `Freeverb` setting, unscaled, for showing
+/// in a UI or saving as a preset.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FreeverbParams {
+    pub wet: f32,
+    pub dry: f32,
+    pub room_size: f32,
+    pub dampening: f32,
+    pub width: f32,
+    pub frozen: bool,
+}
+
+impl Default for FreeverbParams {
+    fn default() -> Self {
+        Self {
+            wet: 1.0,
+            dry: 0.,
+            room_size: 0.5,
+            dampening: 0.5,
+            width: 0.5,
+            frozen: false,
+        }
+    }
 }
 
 impl<
-        const COMB_TUNING_L1: usize,
-        const COMB_TUNING_R1: usize,
-        const COMB_TUNING_L2: usize,
-        const COMB_TUNING_R2: usize,
-        const COMB_TUNING_L3: usize,
-        const COMB_TUNING_R3: usize,
-        const COMB_TUNING_L4: usize,
-        const COMB_TUNING_R4: usize,
-        const COMB_TUNING_L5: usize,
-        const COMB_TUNING_R5: usize,
-        const COMB_TUNING_L6: usize,
-        const COMB_TUNING_R6: usize,
-        const COMB_TUNING_L7: usize,
-        const COMB_TUNING_R7: usize,
-        const COMB_TUNING_L8: usize,
-        const COMB_TUNING_R8: usize,
-        const ALLPASS_TUNING_L1: usize,
-        const ALLPASS_TUNING_R1: usize,
-        const ALLPASS_TUNING_L2: usize,
-        const ALLPASS_TUNING_R2: usize,
-        const ALLPASS_TUNING_L3: usize,
-        const ALLPASS_TUNING_R3: usize,
-        const ALLPASS_TUNING_L4: usize,
-        const ALLPASS_TUNING_R4: usize,
-    >
-    Freeverb<
-        COMB_TUNING_L1,
-        COMB_TUNING_R1,
-        COMB_TUNING_L2,
-        COMB_TUNING_R2,
-        COMB_TUNING_L3,
-        COMB_TUNING_R3,
-        COMB_TUNING_L4,
-        COMB_TUNING_R4,
-        COMB_TUNING_L5,
-        COMB_TUNING_R5,
-        COMB_TUNING_L6,
-        COMB_TUNING_R6,
-        COMB_TUNING_L7,
-        COMB_TUNING_R7,
-        COMB_TUNING_L8,
-        COMB_TUNING_R8,
-        ALLPASS_TUNING_L1,
-        ALLPASS_TUNING_R1,
-        ALLPASS_TUNING_L2,
-        ALLPASS_TUNING_R2,
-        ALLPASS_TUNING_L3,
-        ALLPASS_TUNING_R3,
-        ALLPASS_TUNING_L4,
-        ALLPASS_TUNING_R4,
-    >
+        const COMBS: usize,
+        const ALLPASSES: usize,
+        const COMB_SIZE: usize,
+        const ALLPASS_SIZE: usize,
+    > Freeverb<COMBS, ALLPASSES, COMB_SIZE, ALLPASS_SIZE>
 {
-    pub fn new() -> Self {
-        let mut freeverb = Self {
-            comb_1: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_2: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_3: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_4: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_5: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_6: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_7: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-            comb_8: (LowpassFeedbackComb::new(), LowpassFeedbackComb::new()),
-
-            allpass_1: (AllPass::new(), AllPass::new()),
-            allpass_2: (AllPass::new(), AllPass::new()),
-            allpass_3: (AllPass::new(), AllPass::new()),
-            allpass_4: (AllPass::new(), AllPass::new()),
+    pub fn with_tuning(tuning: FreeverbTuning<COMBS, ALLPASSES>) -> Self {
+        assert!(tuning.comb_size() <= COMB_SIZE);
+        assert!(tuning.allpass_size() <= ALLPASS_SIZE);
 
+        let spread = tuning.stereo_spread;
+
+        let mut freeverb = Self {
+            combs: core::array::from_fn(|i| {
+                (
+                    LowpassFeedbackComb::with_length(tuning.combs[i]),
+                    LowpassFeedbackComb::with_length(tuning.combs[i] + spread),
+                )
+            }),
+            allpasses: core::array::from_fn(|i| {
+                (
+                    AllPass::with_length(tuning.allpasses[i]),
+                    AllPass::with_length(tuning.allpasses[i] + spread),
+                )
+            }),
+
+            params: FreeverbParams::default(),
             wet_gains: (0., 0.),
-            wet: 0.,
-            width: 0.,
-            dry: 0.,
             input_gain: 0.,
-            dampening: 0.,
-            room_size: 0.,
-            frozen: false,
         };
 
-        freeverb.set_wet(1.0);
-        freeverb.set_width(0.5);
-        freeverb.set_dampening(0.5);
-        freeverb.set_room_size(0.5);
-        freeverb.set_frozen(false);
+        freeverb.set_params(FreeverbParams::default());
 
         freeverb
     }
 
+    pub fn params(&self) -> FreeverbParams {
+        self.params
+    }
+
+    pub fn set_params(&mut self, params: FreeverbParams) {
+        self.params = params;
+        self.input_gain = if params.frozen { 0. } else { 1. };
+        self.update_wet_gains();
+        self.update_combs();
+    }
+
+    pub fn dampening(&self) -> f32 {
+        self.params.dampening
+    }
+
     pub fn set_dampening(&mut self, value: f32) {
-        self.dampening = value * SCALE_DAMPENING;
+        self.params.dampening = value;
         self.update_combs();
     }
 
+    pub fn frozen(&self) -> bool {
+        self.params.frozen
+    }
+
     pub fn set_freeze(&mut self, frozen: bool) {
-        self.frozen = frozen;
+        self.params.frozen = frozen;
+        self.input_gain = if frozen { 0. } else { 1. };
         self.update_combs();
     }
 
+    pub fn wet(&self) -> f32 {
+        self.params.wet
+    }
+
     pub fn set_wet(&mut self, value: f32) {
-        self.wet = value * SCALE_WET;
+        self.params.wet = value;
         self.update_wet_gains();
     }
 
+    pub fn width(&self) -> f32 {
+        self.params.width
+    }
+
     pub fn set_width(&mut self, value: f32) {
-        self.width = value;
+        self.params.width = value;
         self.update_wet_gains();
     }
 
     fn update_wet_gains(&mut self) {
-        self.wet_gains = (
-            self.wet * (self.width / 2.0 + 0.5),
-            self.wet * ((1.0 - self.width) / 2.0),
-        )
+        let wet = self.params.wet * SCALE_WET;
+        let width = self.params.width;
+
+        self.wet_gains = (wet * (width / 2.0 + 0.5), wet * ((1.0 - width) / 2.0))
     }
 
-    fn set_frozen(&mut self, frozen: bool) {
-        self.frozen = frozen;
-        self.input_gain = if frozen { 0. } else { 1. };
-        self.update_combs();
+    pub fn room_size(&self) -> f32 {
+        self.params.room_size
     }
 
     pub fn set_room_size(&mut self, value: f32) {
-        self.room_size = value * SCALE_ROOM + OFFSET_ROOM;
+        self.params.room_size = value;
         self.update_combs();
     }
 
     fn update_combs(&mut self) {
-        let (feedback, dampening) = if self.frozen {
+        let (feedback, dampening) = if self.params.frozen {
             (1., 0.)
         } else {
-            (self.room_size, self.dampening)
+            (
+                self.params.room_size * SCALE_ROOM + OFFSET_ROOM,
+                self.params.dampening * SCALE_DAMPENING,
+            )
         };
 
-        self.comb_1.0.set_feedback(feedback);
-        self.comb_1.0.set_dampening(dampening);
-        self.comb_1.1.set_feedback(feedback);
-        self.comb_1.1.set_dampening(dampening);
-
-        self.comb_2.0.set_feedback(feedback);
-        self.comb_2.0.set_dampening(dampening);
-        self.comb_2.1.set_feedback(feedback);
-        self.comb_2.1.set_dampening(dampening);
-
-        self.comb_3.0.set_feedback(feedback);
-        self.comb_3.0.set_dampening(dampening);
-        self.comb_3.1.set_feedback(feedback);
-        self.comb_3.1.set_dampening(dampening);
-
-        self.comb_4.0.set_feedback(feedback);
-        self.comb_4.0.set_dampening(dampening);
-        self.comb_4.1.set_feedback(feedback);
-        self.comb_4.1.set_dampening(dampening);
-
-        self.comb_5.0.set_feedback(feedback);
-        self.comb_5.0.set_dampening(dampening);
-        self.comb_5.1.set_feedback(feedback);
-        self.comb_5.1.set_dampening(dampening);
-
-        self.comb_6.0.set_feedback(feedback);
-        self.comb_6.0.set_dampening(dampening);
-        self.comb_6.1.set_feedback(feedback);
-        self.comb_6.1.set_dampening(dampening);
-
-        self.comb_7.0.set_feedback(feedback);
-        self.comb_7.0.set_dampening(dampening);
-        self.comb_7.1.set_feedback(feedback);
-        self.comb_7.1.set_dampening(dampening);
-
-        self.comb_8.0.set_feedback(feedback);
-        self.comb_8.0.set_dampening(dampening);
-        self.comb_8.1.set_feedback(feedback);
-        self.comb_8.1.set_dampening(dampening);
+        for comb in self.combs.iter_mut() {
+            comb.0.set_feedback(feedback);
+            comb.0.set_dampening(dampening);
+            comb.1.set_feedback(feedback);
+            comb.1.set_dampening(dampening);
+        }
+    }
+
+    pub fn dry(&self) -> f32 {
+        self.params.dry
     }
 
     pub fn set_dry(&mut self, value: f32) {
-        self.dry = value;
+        self.params.dry = value;
     }
 }
 
 impl<
-        const COMB_TUNING_L1: usize,
-        const COMB_TUNING_R1: usize,
-        const COMB_TUNING_L2: usize,
-        const COMB_TUNING_R2: usize,
-        const COMB_TUNING_L3: usize,
-        const COMB_TUNING_R3: usize,
-        const COMB_TUNING_L4: usize,
-        const COMB_TUNING_R4: usize,
-        const COMB_TUNING_L5: usize,
-        const COMB_TUNING_R5: usize,
-        const COMB_TUNING_L6: usize,
-        const COMB_TUNING_R6: usize,
-        const COMB_TUNING_L7: usize,
-        const COMB_TUNING_R7: usize,
-        const COMB_TUNING_L8: usize,
-        const COMB_TUNING_R8: usize,
-        const ALLPASS_TUNING_L1: usize,
-        const ALLPASS_TUNING_R1: usize,
-        const ALLPASS_TUNING_L2: usize,
-        const ALLPASS_TUNING_R2: usize,
-        const ALLPASS_TUNING_L3: usize,
-        const ALLPASS_TUNING_R3: usize,
-        const ALLPASS_TUNING_L4: usize,
-        const ALLPASS_TUNING_R4: usize,
-    > Node<(f32, f32), (f32, f32)>
-    for Freeverb<
-        COMB_TUNING_L1,
-        COMB_TUNING_R1,
-        COMB_TUNING_L2,
-        COMB_TUNING_R2,
-        COMB_TUNING_L3,
-        COMB_TUNING_R3,
-        COMB_TUNING_L4,
-        COMB_TUNING_R4,
-        COMB_TUNING_L5,
-        COMB_TUNING_R5,
-        COMB_TUNING_L6,
-        COMB_TUNING_R6,
-        COMB_TUNING_L7,
-        COMB_TUNING_R7,
-        COMB_TUNING_L8,
-        COMB_TUNING_R8,
-        ALLPASS_TUNING_L1,
-        ALLPASS_TUNING_R1,
-        ALLPASS_TUNING_L2,
-        ALLPASS_TUNING_R2,
-        ALLPASS_TUNING_L3,
-        ALLPASS_TUNING_R3,
-        ALLPASS_TUNING_L4,
-        ALLPASS_TUNING_R4,
-    >
+        const COMBS: usize,
+        const ALLPASSES: usize,
+        const COMB_SIZE: usize,
+        const ALLPASS_SIZE: usize,
+    > Node<(f32, f32), (f32, f32)> for Freeverb<COMBS, ALLPASSES, COMB_SIZE, ALLPASS_SIZE>
 {
     #[inline]
     fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
@@ -348,72 +265,245 @@ impl<
 
         let mut output = (0., 0.);
 
-        output.0 += self.comb_1.0.process(input_mixed);
-        output.1 += self.comb_1.1.process(input_mixed);
+        for comb in self.combs.iter_mut() {
+            output.0 += comb.0.process(input_mixed);
+            output.1 += comb.1.process(input_mixed);
+        }
+
+        for allpass in self.allpasses.iter_mut() {
+            output.0 = allpass.0.process(output.0);
+            output.1 = allpass.1.process(output.1);
+        }
+
+        (
+            output.0 * self.wet_gains.0 + output.1 * self.wet_gains.1 + input.0 * self.params.dry,
+            output.1 * self.wet_gains.0 + output.0 * self.wet_gains.1 + input.1 * self.params.dry,
+        )
+    }
+}
+
+// Declares a `Freeverb` type sized for `$tuning`, with `new` and `Default`
+// using that tuning.
+macro_rules! freeverb_preset {
+    ($name:ident, $tuning:ident) => {
+        pub type $name = Freeverb<8, 4, { $tuning.comb_size() }, { $tuning.allpass_size() }>;
+
+        impl Freeverb<8, 4, { $tuning.comb_size() }, { $tuning.allpass_size() }> {
+            pub fn new() -> Self {
+                Self::with_tuning($tuning)
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
 
-        output.0 += self.comb_2.0.process(input_mixed);
-        output.1 += self.comb_2.1.process(input_mixed);
+freeverb_preset!(Freeverb44100, TUNING_44100);
+freeverb_preset!(Freeverb48000, TUNING_48000);
+freeverb_preset!(Freeverb88200, TUNING_88200);
+freeverb_preset!(Freeverb96000, TUNING_96000);
+freeverb_preset!(Freeverb192000, TUNING_192000);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_tuning() {
+        assert_eq!(TUNING_44100.scaled(44100).combs, TUNING_44100.combs);
+        assert_eq!(TUNING_48000.combs[0], 1215);
+        assert_eq!(TUNING_48000.combs[0] + TUNING_48000.stereo_spread, 1240);
+        assert_eq!(TUNING_88200.combs[0], 2 * TUNING_44100.combs[0]);
+        assert_eq!(TUNING_96000.allpasses[3], 490);
+        assert_eq!(TUNING_192000.combs[7], 7040);
+    }
 
-        output.0 += self.comb_3.0.process(input_mixed);
-        output.1 += self.comb_3.1.process(input_mixed);
+    #[test]
+    fn test_scaled_presets_process() {
+        let mut freeverb = Freeverb96000::new();
 
-        output.0 += self.comb_4.0.process(input_mixed);
-        output.1 += self.comb_4.1.process(input_mixed);
+        let mut energy = 0.;
+        for i in 0..20000 {
+            let input = if i == 0 { 1. } else { 0. };
+            let (left, right) = freeverb.process((input, input));
+            energy += left * left + right * right;
+        }
 
-        output.0 += self.comb_5.0.process(input_mixed);
-        output.1 += self.comb_5.1.process(input_mixed);
+        assert!(energy > 0.);
+    }
 
-        output.0 += self.comb_6.0.process(input_mixed);
-        output.1 += self.comb_6.1.process(input_mixed);
+    #[test]
+    fn test_custom_stages() {
+        const TUNING: FreeverbTuning<4, 2> = FreeverbTuning {
+            combs: [1116, 1277, 1422, 1557],
+            allpasses: [556, 341],
+            stereo_spread: 23,
+        };
 
-        output.0 += self.comb_7.0.process(input_mixed);
-        output.1 += self.comb_7.1.process(input_mixed);
+        let mut freeverb: Freeverb<4, 2, { TUNING.comb_size() }, { TUNING.allpass_size() }> =
+            Freeverb::with_tuning(TUNING);
 
-        output.0 += self.comb_8.0.process(input_mixed);
-        output.1 += self.comb_8.1.process(input_mixed);
+        // Nothing reaches the output before the shortest comb has delayed it
+        assert_eq!(freeverb.process((1., 1.)), (0., 0.));
+        for _ in 1..1116 {
+            assert_eq!(freeverb.process((0., 0.)), (0., 0.));
+        }
+        assert_ne!(freeverb.process((0., 0.)), (0., 0.));
+    }
 
-        output.0 = self.allpass_1.0.process(output.0);
-        output.1 = self.allpass_1.1.process(output.1);
+    // Runs the same noise burst through `freeverb` that was used to generate
+    // the golden output from Jezar's reference C++ implementation, returning
+    // an FNV-1a hash of the output bits.
+    fn reference_run(
+        freeverb: &mut Freeverb44100,
+        noise_length: usize,
+        freeze: Option<(usize, usize)>,
+        checkpoints: &[(usize, (f32, f32))],
+    ) -> u64 {
+        freeverb.set_wet(1. / 3.);
+        freeverb.set_room_size(0.8);
+        freeverb.set_dampening(0.3);
+        // The reference scales dry by 2, this matches its setdry(0.25)
+        freeverb.set_dry(0.5);
+        freeverb.set_width(0.3);
+
+        let mut seed: u32 = 1;
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        for i in 0..16384 {
+            if let Some((start, end)) = freeze {
+                if i == start {
+                    freeverb.set_freeze(true);
+                } else if i == end {
+                    freeverb.set_freeze(false);
+                }
+            }
+
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+
+            let left = if i < noise_length {
+                (seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+            } else {
+                0.
+            };
+
+            let (a, b) = freeverb.process((left, -left * 0.5));
+
+            for bits in [a.to_bits(), b.to_bits()].iter() {
+                hash ^= *bits as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+
+            for &(index, (expected_a, expected_b)) in checkpoints.iter() {
+                if index == i {
+                    assert!((a - expected_a).abs() < 1e-8);
+                    assert!((b - expected_b).abs() < 1e-8);
+                }
+            }
+        }
+
+        hash
+    }
 
-        output.0 = self.allpass_2.0.process(output.0);
-        output.1 = self.allpass_2.1.process(output.1);
+    #[test]
+    fn test_matches_reference() {
+        let mut freeverb = Freeverb44100::new();
+
+        let hash = reference_run(
+            &mut freeverb,
+            2000,
+            None,
+            &[
+                (3000, (0.014799342, -0.014969541)),
+                (7000, (-0.0005292976, 0.011648027)),
+                (10000, (-0.007274536, -0.0064130784)),
+                (13000, (-0.010400164, -0.0072563137)),
+            ],
+        );
+
+        assert_eq!(hash, 0x13c565038f9abab0);
+    }
 
-        output.0 = self.allpass_3.0.process(output.0);
-        output.1 = self.allpass_3.1.process(output.1);
+    #[test]
+    fn test_freeze_matches_reference() {
+        let mut freeverb = Freeverb44100::new();
+
+        // Input keeps going while frozen, which must not reach the tail
+        let hash = reference_run(
+            &mut freeverb,
+            9000,
+            Some((6000, 12000)),
+            &[
+                (3000, (0.22905372, -0.12209673)),
+                (7000, (0.17882074, -0.02680432)),
+                (10000, (0.0056888424, -0.024603467)),
+                (13000, (-0.06757193, -0.078807205)),
+            ],
+        );
+
+        assert_eq!(hash, 0x69f666d7e432bcb);
+    }
 
-        output.0 = self.allpass_4.0.process(output.0);
-        output.1 = self.allpass_4.1.process(output.1);
+    #[test]
+    fn test_params() {
+        let mut freeverb = Freeverb44100::new();
+        assert_eq!(freeverb.params(), FreeverbParams::default());
+
+        freeverb.set_wet(0.3);
+        freeverb.set_dry(0.7);
+        freeverb.set_room_size(0.9);
+        freeverb.set_dampening(0.1);
+        freeverb.set_width(0.8);
+        freeverb.set_freeze(true);
+
+        assert_eq!(freeverb.wet(), 0.3);
+        assert_eq!(freeverb.dry(), 0.7);
+        assert_eq!(freeverb.room_size(), 0.9);
+        assert_eq!(freeverb.dampening(), 0.1);
+        assert_eq!(freeverb.width(), 0.8);
+        assert!(freeverb.frozen());
+
+        let preset = freeverb.params();
+
+        let mut other = Freeverb44100::new();
+        other.set_params(preset);
+        assert_eq!(other.params(), preset);
+
+        // Applying a preset behaves the same as calling each setter
+        freeverb.set_freeze(false);
+        other.set_freeze(false);
+        for i in 0..5000 {
+            let input = if i == 0 { (1., 0.5) } else { (0., 0.) };
+            assert_eq!(freeverb.process(input), other.process(input));
+        }
+    }
 
-        (
-            output.0 * self.wet_gains.0 + output.1 * self.wet_gains.1 + input.0 * self.dry,
-            output.1 * self.wet_gains.1 + output.0 * self.wet_gains.0 + input.1 * self.dry,
-        )
+    #[test]
+    fn test_width() {
+        let mut freeverb = Freeverb44100::new();
+        freeverb.set_width(0.);
+
+        // With no width both channels get the same mix
+        for i in 0..5000 {
+            let input = if i == 0 { (1., 0.) } else { (0., 0.) };
+            let (left, right) = freeverb.process(input);
+            assert_eq!(left, right);
+        }
+
+        let mut freeverb = Freeverb44100::new();
+        freeverb.set_width(1.);
+
+        let mut difference = 0.;
+        for i in 0..5000 {
+            let input = if i == 0 { (1., 0.) } else { (0., 0.) };
+            let (left, right) = freeverb.process(input);
+            difference += (left - right).abs();
+        }
+
+        assert!(difference > 0.);
     }
 }
-
-pub type Freeverb44100<T> = Freeverb<
-    COMB_TUNING_L1,
-    COMB_TUNING_R1,
-    COMB_TUNING_L2,
-    COMB_TUNING_R2,
-    COMB_TUNING_L3,
-    COMB_TUNING_R3,
-    COMB_TUNING_L4,
-    COMB_TUNING_R4,
-    COMB_TUNING_L5,
-    COMB_TUNING_R5,
-    COMB_TUNING_L6,
-    COMB_TUNING_R6,
-    COMB_TUNING_L7,
-    COMB_TUNING_R7,
-    COMB_TUNING_L8,
-    COMB_TUNING_R8,
-    ALLPASS_TUNING_L1,
-    ALLPASS_TUNING_R1,
-    ALLPASS_TUNING_L2,
-    ALLPASS_TUNING_R2,
-    ALLPASS_TUNING_L3,
-    ALLPASS_TUNING_R3,
-    ALLPASS_TUNING_L4,
-    ALLPASS_TUNING_R4,
->;
@@ -0,0 +1,348 @@
+use super::{Node, Random, ReadableNode};
+use core::f32::consts::PI;
+use libm;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LfoShape {
+    Sine,
+    /// Rises from -1 to 1 over the first half of each cycle, then falls.
+    Triangle,
+    /// Rises from -1 to 1 over each cycle.
+    Saw,
+    Square,
+    /// A new random level at the start of each cycle.
+    SampleAndHold,
+    /// Glides smoothly from one random level to the next over each cycle.
+    SmoothRandom,
+}
+
+/// A note length for tempo sync, by its note value, so `Straight(4)` is a
+/// quarter note and `Triplet(8)` an eighth note triplet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Division {
+    Straight(u32),
+    Dotted(u32),
+    Triplet(u32),
+}
+
+impl Division {
+    /// The length in quarter note beats.
+    pub fn beats(&self) -> f32 {
+        match *self {
+            Division::Straight(note) => 4. / note as f32,
+            Division::Dotted(note) => 6. / note as f32,
+            Division::Triplet(note) => 8. / (3 * note) as f32,
+        }
+    }
+}
+
+/// A low frequency oscillator for modulation. `process` moves it on by one
+/// sample and `read` gives its current value.
+pub struct Lfo {
+    sample_rate: f32,
+    frequency: f32,
+    shape: LfoShape,
+    phase_offset: f32,
+    unipolar: bool,
+
+    phase: f32,
+    random: Random,
+    previous: f32,
+    target: f32,
+    // Where through the cycle the current glide began.
+    glide_start: f32,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: f32) -> Self {
+        Self::with_seed(sample_rate, 1)
+    }
+
+    /// Like `new`, with a seed for the random shapes.
+    pub fn with_seed(sample_rate: f32, seed: u64) -> Self {
+        assert!(sample_rate > 0.);
+
+        let mut lfo = Self {
+            sample_rate,
+            frequency: 1.,
+            shape: LfoShape::Sine,
+            phase_offset: 0.,
+            unipolar: false,
+
+            phase: 0.,
+            random: Random::new(seed),
+            previous: 0.,
+            target: 0.,
+            glide_start: 0.,
+        };
+
+        lfo.target = lfo.next_random();
+
+        lfo
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        assert!(sample_rate > 0. && self.frequency < sample_rate);
+
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Set the frequency in Hz.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        assert!(frequency >= 0. && frequency < self.sample_rate);
+
+        self.frequency = frequency;
+    }
+
+    /// Set the frequency so that each cycle lasts `division` at `bpm`.
+    pub fn set_tempo(&mut self, bpm: f32, division: Division) {
+        assert!(bpm > 0.);
+
+        self.set_frequency(bpm / 60. / division.beats());
+    }
+
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn phase_offset(&self) -> f32 {
+        self.phase_offset
+    }
+
+    /// Shift the waveform by a fraction of a cycle, such as 0.25 between two
+    /// LFOs for quadrature modulation.
+    pub fn set_phase_offset(&mut self, offset: f32) {
+        assert!((0. ..1.).contains(&offset));
+
+        self.phase_offset = offset;
+    }
+
+    pub fn unipolar(&self) -> bool {
+        self.unipolar
+    }
+
+    /// Output from 0 to 1 instead of from -1 to 1.
+    pub fn set_unipolar(&mut self, unipolar: bool) {
+        self.unipolar = unipolar;
+    }
+
+    /// Restart the cycle, such as on a new note. The random shapes carry on
+    /// from their current level towards a new one, reaching it at the end of
+    /// the cycle, so they don't click whatever the phase offset.
+    pub fn reset(&mut self) {
+        self.previous = self.value();
+        self.target = self.next_random();
+        self.phase = 0.;
+        self.glide_start = self.position();
+    }
+
+    // Where the waveform is through its cycle, including the offset.
+    fn position(&self) -> f32 {
+        let position = self.phase + self.phase_offset;
+        if position >= 1. {
+            position - 1.
+        } else {
+            position
+        }
+    }
+
+    fn next_random(&mut self) -> f32 {
+        self.random.uniform() * 2. - 1.
+    }
+
+    // The current value from -1 to 1, before any unipolar scaling.
+    fn value(&self) -> f32 {
+        let position = self.position();
+
+        match self.shape {
+            LfoShape::Sine => libm::sinf(2. * PI * position),
+            LfoShape::Triangle => 1. - 4. * (position - 0.5).abs(),
+            LfoShape::Saw => 2. * position - 1.,
+            LfoShape::Square => {
+                if position < 0.5 {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            LfoShape::SampleAndHold => self.target,
+            LfoShape::SmoothRandom => {
+                let progress = (position - self.glide_start) / (1. - self.glide_start);
+                let glide = (1. - libm::cosf(PI * progress.clamp(0., 1.))) / 2.;
+                self.previous + (self.target - self.previous) * glide
+            }
+        }
+    }
+}
+
+impl Node<(), f32> for Lfo {
+    #[inline]
+    fn process(&mut self, _: ()) -> f32 {
+        let before = self.position();
+
+        self.phase += self.frequency / self.sample_rate;
+        if self.phase >= 1. {
+            self.phase -= 1.;
+        }
+
+        if self.position() < before {
+            self.previous = self.target;
+            self.target = self.next_random();
+            self.glide_start = 0.;
+        }
+
+        self.read()
+    }
+}
+
+impl ReadableNode<f32> for Lfo {
+    #[inline]
+    fn read(&self) -> f32 {
+        let value = self.value();
+
+        if self.unipolar {
+            (value + 1.) / 2.
+        } else {
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes() {
+        let mut lfo = Lfo::new(1000.);
+        lfo.set_frequency(10.);
+
+        for n in 1..200 {
+            let expected = libm::sinf(2. * PI * 10. * n as f32 / 1000.);
+            assert!((lfo.process(()) - expected).abs() < 1e-3);
+        }
+
+        // A quarter of the way through a cycle
+        let expected = [
+            (LfoShape::Triangle, 0.),
+            (LfoShape::Saw, -0.5),
+            (LfoShape::Square, 1.),
+        ];
+        for &(shape, value) in expected.iter() {
+            lfo.set_shape(shape);
+            lfo.reset();
+            for _ in 0..25 {
+                lfo.process(());
+            }
+            assert!((lfo.read() - value).abs() < 1e-3, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn test_tempo() {
+        let mut lfo = Lfo::new(48000.);
+
+        lfo.set_tempo(120., Division::Straight(4));
+        assert_eq!(lfo.frequency(), 2.);
+        lfo.set_tempo(120., Division::Straight(1));
+        assert_eq!(lfo.frequency(), 0.5);
+        lfo.set_tempo(120., Division::Dotted(8));
+        assert!((lfo.frequency() - 8. / 3.).abs() < 1e-5);
+        lfo.set_tempo(90., Division::Triplet(8));
+        assert!((lfo.frequency() - 4.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_offset_and_polarity() {
+        let mut lfo = Lfo::new(1000.);
+        lfo.set_frequency(10.);
+        lfo.set_phase_offset(0.25);
+        lfo.set_unipolar(true);
+
+        assert_eq!(lfo.read(), 1.);
+        for n in 1..200 {
+            let expected = (libm::cosf(2. * PI * 10. * n as f32 / 1000.) + 1.) / 2.;
+            assert!((lfo.process(()) - expected).abs() < 1e-3);
+        }
+
+        lfo.reset();
+        assert_eq!(lfo.read(), 1.);
+    }
+
+    #[test]
+    fn test_random_shapes() {
+        // A cycle is exactly 128 samples
+        let mut held = Lfo::with_seed(1024., 7);
+        let mut smooth = Lfo::with_seed(1024., 7);
+        held.set_shape(LfoShape::SampleAndHold);
+        smooth.set_shape(LfoShape::SmoothRandom);
+        held.set_frequency(8.);
+        smooth.set_frequency(8.);
+
+        let mut changes = 0;
+        let mut previous = (held.read(), smooth.read());
+        for n in 1..=1000 {
+            let (a, b) = (held.process(()), smooth.process(()));
+            assert!(a.abs() <= 1. && b.abs() <= 1.);
+
+            // Glides no faster than a half cosine between opposite extremes
+            assert!((b - previous.1).abs() < 0.03, "{} {}", b, previous.1);
+
+            if a != previous.0 {
+                changes += 1;
+                assert_eq!(n % 128, 0);
+                // The smooth shape starts from the level just finished
+                assert!((b - previous.0).abs() < 1e-6);
+            }
+            previous = (a, b);
+        }
+
+        assert!(changes >= 6);
+    }
+
+    #[test]
+    fn test_reset_random() {
+        for &offset in [0., 0.5].iter() {
+            let mut lfo = Lfo::with_seed(1024., 3);
+            lfo.set_shape(LfoShape::SmoothRandom);
+            lfo.set_frequency(8.);
+            lfo.set_phase_offset(offset);
+            lfo.set_unipolar(true);
+
+            for _ in 0..50 {
+                lfo.process(());
+            }
+
+            // Glides on from where it was instead of jumping back
+            let before = lfo.read();
+            lfo.reset();
+            assert!((lfo.read() - before).abs() < 1e-6, "{}", offset);
+            assert!((lfo.process(()) - before).abs() < 0.03, "{}", offset);
+
+            // And heads for a new level by the end of the cycle
+            lfo.set_shape(LfoShape::SampleAndHold);
+            let target = lfo.read();
+            lfo.set_shape(LfoShape::SmoothRandom);
+            let remaining = (128. * (1. - offset)) as usize;
+            for _ in 2..remaining {
+                lfo.process(());
+            }
+            assert!((lfo.read() - target).abs() < 1e-3, "{}", offset);
+
+            lfo.process(());
+            assert_eq!(lfo.read(), target);
+        }
+    }
+}
@@ -1,27 +1,26 @@
-use super::{DelayLine, Node, ReadableNode};
+use super::{DelayLine, Node, OnePole, ReadableNode};
 
 pub struct LowpassFeedbackComb<const N: usize> {
     delay_line: DelayLine<N>,
     feedback: f32,
-    filter_state: f32,
-    dampening: f32,
-    dampening_inverse: f32,
+    filter: OnePole,
 }
 
 impl<const N: usize> LowpassFeedbackComb<N> {
     pub fn new() -> Self {
+        Self::with_length(N)
+    }
+
+    pub fn with_length(length: usize) -> Self {
         Self {
-            delay_line: DelayLine::new(),
+            delay_line: DelayLine::with_length(length),
             feedback: 0.5,
-            filter_state: 0.,
-            dampening: 0.5,
-            dampening_inverse: 0.5,
+            filter: OnePole::new(0.5),
         }
     }
 
     pub fn set_dampening(&mut self, value: f32) {
-        self.dampening = value;
-        self.dampening_inverse = 1. - value;
+        self.filter.set_coefficient(value);
     }
 
     pub fn set_feedback(&mut self, value: f32) {
@@ -33,10 +32,9 @@ impl<const N: usize> Node<f32, f32> for LowpassFeedbackComb<N> {
     fn process(&mut self, input: f32) -> f32 {
         let output = self.delay_line.read();
 
-        self.filter_state = output * self.dampening_inverse + self.filter_state * self.dampening;
+        let filtered = self.filter.process(output);
 
-        self.delay_line
-            .process(input + self.filter_state * self.feedback);
+        self.delay_line.process(input + filtered * self.feedback);
 
         output
     }
@@ -1,8 +1,28 @@
 pub mod all_pass;
 pub mod delay_line;
+pub mod one_pole;
 pub mod lowpass_feedback_comb;
 pub mod freeverb;
 pub mod biquad;
+pub mod early_reflections;
+pub mod room;
+pub mod fdn;
+pub mod plate;
+pub mod fft;
+pub mod convolution;
+pub mod stft;
+pub mod window;
+pub mod fir;
+pub mod resampler;
+pub mod oversample;
+pub mod waveshaper;
+pub mod bitcrusher;
+pub mod oscillator;
+pub mod wavetable;
+pub mod noise;
+pub mod envelope;
+pub mod lfo;
+pub mod envelope_follower;
 
 pub(crate) use super::node::*;
 
@@ -10,5 +30,25 @@ pub use all_pass::*;
 pub use freeverb::*;
 pub use lowpass_feedback_comb::*;
 pub use delay_line::*;
+pub use one_pole::*;
+pub use early_reflections::*;
+pub use room::*;
+pub use fdn::*;
+pub use plate::*;
+pub use fft::*;
+pub use convolution::*;
+pub use stft::*;
+pub use window::*;
+pub use fir::*;
+pub use resampler::*;
+pub use oversample::*;
+pub use waveshaper::*;
+pub use bitcrusher::*;
+pub use oscillator::*;
+pub use wavetable::*;
+pub use noise::*;
+pub use envelope::*;
+pub use lfo::*;
+pub use envelope_follower::*;
 
 
@@ -0,0 +1,81 @@
+use super::{Node, ReadableNode};
+use libm;
+
+/// A one-pole lowpass, moving `1 - coefficient` of the way towards the input
+/// each sample. Used for damping and for smoothing levels and parameters.
+pub struct OnePole {
+    coefficient: f32,
+    coefficient_inverse: f32,
+    state: f32,
+}
+
+impl OnePole {
+    pub fn new(coefficient: f32) -> Self {
+        let mut one_pole = Self {
+            coefficient: 0.,
+            coefficient_inverse: 1.,
+            state: 0.,
+        };
+
+        one_pole.set_coefficient(coefficient);
+
+        one_pole
+    }
+
+    /// The coefficient that covers `1 - 1/e` of a step in `time`
+    /// milliseconds, or 0 to follow the input immediately.
+    pub fn time_coefficient(time: f32, sample_rate: f32) -> f32 {
+        let samples = time * sample_rate / 1000.;
+        if samples > 0. {
+            libm::expf(-1. / samples)
+        } else {
+            0.
+        }
+    }
+
+    pub fn coefficient(&self) -> f32 {
+        self.coefficient
+    }
+
+    pub fn set_coefficient(&mut self, coefficient: f32) {
+        self.coefficient = coefficient;
+        self.coefficient_inverse = 1. - coefficient;
+    }
+
+    pub fn reset(&mut self) {
+        self.state = 0.;
+    }
+}
+
+impl Node<f32, f32> for OnePole {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        self.state = input * self.coefficient_inverse + self.state * self.coefficient;
+        self.state
+    }
+}
+
+impl ReadableNode<f32> for OnePole {
+    #[inline]
+    fn read(&self) -> f32 {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_coefficient() {
+        let mut one_pole = OnePole::new(OnePole::time_coefficient(10., 1000.));
+
+        for _ in 0..10 {
+            one_pole.process(1.);
+        }
+        assert!((one_pole.read() - (1. - libm::expf(-1.))).abs() < 1e-5);
+
+        one_pole.set_coefficient(OnePole::time_coefficient(0., 1000.));
+        assert_eq!(one_pole.process(0.25), 0.25);
+    }
+}
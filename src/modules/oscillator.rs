@@ -0,0 +1,504 @@
+use super::{Node, ReadableNode};
+use core::f32::consts::PI;
+use libm;
+
+// Above this, edges come too close together for the two sample corrections
+// to keep up.
+const MAX_FREQUENCY: f32 = 0.49;
+
+const SUPERSAW_VOICES: usize = 7;
+
+// How far each supersaw voice is detuned at full detune, relative to the
+// frequency, after the measurements of the JP-8000 by Adam Szabo. The centre
+// voice is first.
+const SUPERSAW_DETUNE: [f32; SUPERSAW_VOICES] = [
+    0.,
+    -0.110_023_13,
+    -0.062_884_39,
+    -0.019_523_56,
+    0.019_912_21,
+    0.062_165_38,
+    0.107_452_42,
+];
+
+// Spread out so the voices don't start in step.
+const SUPERSAW_PHASES: [f32; SUPERSAW_VOICES] = [0., 0.37, 0.81, 0.14, 0.59, 0.92, 0.26];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    /// Rises from -1 to 1 over each cycle.
+    Saw,
+    /// 1 for the first `pulse_width` of each cycle, then -1.
+    Square,
+    Triangle,
+    /// Seven saws, detuned around the frequency.
+    Supersaw,
+}
+
+// A discontinuity `position` of the way through the cycle, as a jump in value
+// and a change in slope per cycle.
+#[derive(Copy, Clone)]
+struct Edge {
+    position: f32,
+    step: f32,
+    slope: f32,
+}
+
+const NO_EDGE: Edge = Edge {
+    position: 2.,
+    step: 0.,
+    slope: 0.,
+};
+
+impl Waveform {
+    fn value(self, phase: f32, pulse_width: f32) -> f32 {
+        match self {
+            Waveform::Sine => libm::sinf(2. * PI * phase),
+            Waveform::Saw | Waveform::Supersaw => 2. * phase - 1.,
+            Waveform::Square => {
+                if phase < pulse_width {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            Waveform::Triangle => 1. - 4. * (phase - 0.5).abs(),
+        }
+    }
+
+    // The slope per cycle.
+    fn slope(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => 2. * PI * libm::cosf(2. * PI * phase),
+            Waveform::Saw | Waveform::Supersaw => 2.,
+            Waveform::Square => 0.,
+            Waveform::Triangle => {
+                if phase < 0.5 {
+                    4.
+                } else {
+                    -4.
+                }
+            }
+        }
+    }
+
+    fn edges(self, pulse_width: f32) -> [Edge; 2] {
+        let edge = |position, step, slope| Edge {
+            position,
+            step,
+            slope,
+        };
+
+        match self {
+            Waveform::Sine => [NO_EDGE, NO_EDGE],
+            Waveform::Saw | Waveform::Supersaw => [edge(1., -2., 0.), NO_EDGE],
+            Waveform::Square => [edge(pulse_width, -2., 0.), edge(1., 2., 0.)],
+            Waveform::Triangle => [edge(0.5, 0., -8.), edge(1., 0., 8.)],
+        }
+    }
+}
+
+// The PolyBLEP and PolyBLAMP corrections for the samples either side of the
+// interval the edges fall in.
+#[derive(Default)]
+struct Corrections {
+    before: f32,
+    after: f32,
+}
+
+impl Corrections {
+    // `at` is how far through the interval the edge is, and `slope` the
+    // change in slope per sample.
+    #[inline]
+    fn add(&mut self, at: f32, step: f32, slope: f32) {
+        let (before, after) = (1. - at, at);
+
+        self.before += step * before * before / 2. + slope * before * before * before / 6.;
+        self.after += -step * after * after / 2. + slope * after * after * after / 6.;
+    }
+}
+
+/// A band limited oscillator, with edges smoothed by PolyBLEP and corners by
+/// PolyBLAMP. The input to `process` is added to the frequency, for linear
+/// FM.
+///
+/// Frequencies are fractions of the sample rate, up to 0.49. The corrections
+/// reach one sample either side of an edge, so each sample is only output
+/// once the next one is known, without adding any delay to the waveform.
+pub struct Oscillator {
+    waveform: Waveform,
+    frequency: f32,
+    pulse_width: f32,
+    detune: f32,
+    mix: f32,
+
+    phases: [f32; SUPERSAW_VOICES],
+    sync: Option<f32>,
+    wrapped: Option<f32>,
+    pending: f32,
+    output: f32,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform, frequency: f32) -> Self {
+        let mut oscillator = Self {
+            waveform,
+            frequency: 0.,
+            pulse_width: 0.5,
+            detune: 0.5,
+            mix: 0.5,
+
+            phases: SUPERSAW_PHASES,
+            sync: None,
+            wrapped: None,
+            pending: 0.,
+            output: 0.,
+        };
+
+        oscillator.set_frequency(frequency);
+        oscillator.reset();
+
+        oscillator
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        assert!((0. ..=MAX_FREQUENCY).contains(&frequency));
+
+        self.frequency = frequency;
+    }
+
+    pub fn pulse_width(&self) -> f32 {
+        self.pulse_width
+    }
+
+    /// Set the fraction of each cycle the square wave spends high.
+    pub fn set_pulse_width(&mut self, pulse_width: f32) {
+        assert!(pulse_width > 0. && pulse_width < 1.);
+
+        self.pulse_width = pulse_width;
+    }
+
+    pub fn detune(&self) -> f32 {
+        self.detune
+    }
+
+    /// Set how far apart the supersaw voices are, from 0 to 1.
+    pub fn set_detune(&mut self, detune: f32) {
+        assert!((0. ..=1.).contains(&detune));
+
+        self.detune = detune;
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the level of the detuned supersaw voices relative to the centre
+    /// one, from 0 for the centre voice alone to 1 for all of them equally.
+    pub fn set_mix(&mut self, mix: f32) {
+        assert!((0. ..=1.).contains(&mix));
+
+        self.mix = mix;
+    }
+
+    /// How far through the cycle the oscillator is, from 0 to 1.
+    pub fn phase(&self) -> f32 {
+        self.phases[0]
+    }
+
+    /// Jump to `phase` without any smoothing, which will alias if the
+    /// waveform jumps too.
+    pub fn set_phase(&mut self, phase: f32) {
+        assert!((0. ..1.).contains(&phase));
+
+        self.phases[0] = phase;
+        self.pending = self.naive();
+    }
+
+    /// Restart the cycle `at` of the way through the next interval between
+    /// samples, so when the next sample is processed.
+    pub fn sync(&mut self, at: f32) {
+        assert!((0. ..=1.).contains(&at));
+
+        self.sync = Some(at);
+    }
+
+    /// How far through the last interval between samples the cycle restarted,
+    /// if it did. Passing this to `sync` on another oscillator hard syncs it
+    /// to this one.
+    pub fn wrapped(&self) -> Option<f32> {
+        self.wrapped
+    }
+
+    pub fn reset(&mut self) {
+        self.phases = SUPERSAW_PHASES;
+        self.sync = None;
+        self.wrapped = None;
+        self.pending = self.naive();
+        self.output = 0.;
+    }
+
+    fn voices(&self) -> usize {
+        match self.waveform {
+            Waveform::Supersaw => SUPERSAW_VOICES,
+            _ => 1,
+        }
+    }
+
+    // Power stays about the same as the mix changes.
+    fn gain(&self, voice: usize) -> f32 {
+        match self.waveform {
+            Waveform::Supersaw => {
+                let centre = 1. / libm::sqrtf(1. + 6. * self.mix * self.mix);
+                if voice == 0 {
+                    centre
+                } else {
+                    centre * self.mix
+                }
+            }
+            _ => 1.,
+        }
+    }
+
+    fn naive(&self) -> f32 {
+        (0..self.voices())
+            .map(|voice| {
+                self.gain(voice) * self.waveform.value(self.phases[voice], self.pulse_width)
+            })
+            .sum()
+    }
+
+    // Move `phase` on over the interval between samples from `from` to `to`,
+    // adding corrections for any edges it passes. Returns the new phase and
+    // where in the interval it wrapped, if it did.
+    #[inline]
+    fn advance(
+        &self,
+        phase: f32,
+        increment: f32,
+        from: f32,
+        to: f32,
+        gain: f32,
+        corrections: &mut Corrections,
+    ) -> (f32, Option<f32>) {
+        let end = phase + increment * (to - from);
+
+        for edge in self.waveform.edges(self.pulse_width).iter() {
+            for position in [edge.position, edge.position + 1.].iter() {
+                if phase < *position && *position <= end {
+                    let at = from + (position - phase) / increment;
+                    corrections.add(at, gain * edge.step, gain * edge.slope * increment);
+                }
+            }
+        }
+
+        if end >= 1. {
+            (end - 1., Some(from + (1. - phase) / increment))
+        } else {
+            (end, None)
+        }
+    }
+}
+
+impl Node<f32, f32> for Oscillator {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let frequency = (self.frequency + input).clamp(0., MAX_FREQUENCY);
+        let sync = self.sync.take();
+        let mut corrections = Corrections::default();
+        let mut next = 0.;
+
+        for (voice, detune) in SUPERSAW_DETUNE.iter().enumerate().take(self.voices()) {
+            let gain = self.gain(voice);
+            let increment = frequency * (1. + detune * self.detune);
+            let phase = self.phases[voice];
+
+            let (phase, wrapped) = match sync {
+                Some(at) => {
+                    let (phase, _) = self.advance(phase, increment, 0., at, gain, &mut corrections);
+
+                    let step = self.waveform.value(0., self.pulse_width)
+                        - self.waveform.value(phase, self.pulse_width);
+                    let slope = self.waveform.slope(0.) - self.waveform.slope(phase);
+                    corrections.add(at, gain * step, gain * slope * increment);
+
+                    let (phase, _) = self.advance(0., increment, at, 1., gain, &mut corrections);
+                    (phase, Some(at))
+                }
+                None => self.advance(phase, increment, 0., 1., gain, &mut corrections),
+            };
+
+            if voice == 0 {
+                self.wrapped = wrapped;
+            }
+
+            self.phases[voice] = phase;
+            next += gain * self.waveform.value(phase, self.pulse_width);
+        }
+
+        self.output = self.pending + corrections.before;
+        self.pending = next + corrections.after;
+
+        self.output
+    }
+}
+
+impl ReadableNode<f32> for Oscillator {
+    /// The sample most recently returned by `process`.
+    #[inline]
+    fn read(&self) -> f32 {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Complex, Fft};
+    use super::*;
+
+    const LENGTH: usize = 4096;
+    // A little over 3kHz at 44.1kHz, landing exactly on a bin
+    const BIN: usize = 297;
+    const FREQUENCY: f32 = BIN as f32 / LENGTH as f32;
+
+    // The energy in the bins that aren't harmonics of `BIN`, relative to the
+    // fundamental.
+    fn alias_db(signal: &[f32; LENGTH]) -> f32 {
+        let fft = Fft::<LENGTH>::new();
+        let mut spectrum = [Complex::default(); LENGTH];
+        fft.forward_real(signal, &mut spectrum);
+
+        let aliases: f32 = (1..LENGTH / 2)
+            .filter(|bin| bin % BIN != 0)
+            .map(|bin| spectrum[bin].norm_sqr())
+            .sum();
+
+        10. * libm::log10f(aliases / spectrum[BIN].norm_sqr())
+    }
+
+    fn naive(waveform: Waveform, pulse_width: f32) -> [f32; LENGTH] {
+        let mut signal = [0.; LENGTH];
+        for (n, sample) in signal.iter_mut().enumerate() {
+            let phase = (n * BIN % LENGTH) as f32 / LENGTH as f32;
+            *sample = waveform.value(phase, pulse_width);
+        }
+        signal
+    }
+
+    #[test]
+    fn test_sine() {
+        let mut oscillator = Oscillator::new(Waveform::Sine, 0.01);
+
+        for n in 0..1000 {
+            let expected = libm::sinf(2. * PI * 0.01 * n as f32);
+            let output = oscillator.process(0.);
+
+            assert!((output - expected).abs() < 1e-3);
+            assert_eq!(oscillator.read(), output);
+        }
+    }
+
+    #[test]
+    fn test_reduces_aliasing() {
+        for &(waveform, improvement) in [
+            (Waveform::Saw, 12.),
+            (Waveform::Square, 12.),
+            (Waveform::Triangle, 8.),
+        ]
+        .iter()
+        {
+            let mut oscillator = Oscillator::new(waveform, FREQUENCY);
+            oscillator.set_pulse_width(0.3);
+
+            let mut signal = [0.; LENGTH];
+            for sample in signal.iter_mut() {
+                *sample = oscillator.process(0.);
+            }
+
+            let (naive, band_limited) = (alias_db(&naive(waveform, 0.3)), alias_db(&signal));
+            assert!(
+                band_limited < naive - improvement,
+                "{:?} {} {}",
+                waveform,
+                naive,
+                band_limited
+            );
+        }
+    }
+
+    #[test]
+    fn test_hard_sync() {
+        let mut master = Oscillator::new(Waveform::Saw, FREQUENCY);
+        let mut slave = Oscillator::new(Waveform::Triangle, FREQUENCY * 2.37);
+
+        let mut naive = [0.; LENGTH];
+        let mut signal = [0.; LENGTH];
+        let mut phase = 0.;
+        for n in 0..LENGTH {
+            let master_phase = (n * BIN % LENGTH) as f32 / LENGTH as f32;
+            if n > 0 && master_phase < FREQUENCY {
+                phase = master_phase * 2.37;
+            }
+            naive[n] = Waveform::Triangle.value(phase, 0.5);
+            phase += FREQUENCY * 2.37;
+            phase -= libm::floorf(phase);
+
+            master.process(0.);
+            if let Some(at) = master.wrapped() {
+                slave.sync(at);
+            }
+            signal[n] = slave.process(0.);
+        }
+
+        let (naive, band_limited) = (alias_db(&naive), alias_db(&signal));
+        assert!(band_limited < naive - 10., "{} {}", naive, band_limited);
+    }
+
+    #[test]
+    fn test_fm() {
+        let mut modulated = Oscillator::new(Waveform::Square, 0.01);
+        let mut oscillator = Oscillator::new(Waveform::Square, 0.013);
+
+        for _ in 0..1000 {
+            assert!((modulated.process(0.003) - oscillator.process(0.)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_supersaw() {
+        let mut saw = Oscillator::new(Waveform::Saw, FREQUENCY);
+        let mut supersaw = Oscillator::new(Waveform::Supersaw, FREQUENCY);
+        supersaw.set_mix(0.);
+        supersaw.reset();
+
+        for _ in 0..1000 {
+            assert_eq!(saw.process(0.), supersaw.process(0.));
+        }
+
+        supersaw.set_mix(1.);
+        supersaw.set_detune(1.);
+        let mut energy = 0.;
+        for _ in 0..LENGTH {
+            let output = supersaw.process(0.);
+            assert!(output.abs() < 3.);
+            energy += output * output;
+        }
+
+        // Similar power to a single saw, which has a mean square of 1/3
+        let power = energy / LENGTH as f32;
+        assert!(power > 0.2 && power < 0.5, "{}", power);
+    }
+}
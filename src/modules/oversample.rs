@@ -0,0 +1,272 @@
+use super::{Node, Window};
+use crate::math;
+use core::f64::consts::PI;
+
+// Non-zero taps either side of the centre of each half-band filter. Every
+// other tap of a half-band filter is zero, so this gives a 47 tap filter with
+// a centre tap of 0.5 and around 70dB of stopband attenuation.
+const HALF_BAND_TAPS: usize = 24;
+const HALF_BAND_CENTRE: usize = HALF_BAND_TAPS - 1;
+const HALF_BAND_LENGTH: usize = HALF_BAND_TAPS * 2 - 1;
+
+const MAX_STAGES: usize = 3;
+
+// The taps at even indices of the filter, which are the non-zero ones either
+// side of the odd centre tap. Scaled so they sum to 0.5, giving unity gain
+// at DC through either polyphase branch.
+const HALF_BAND: [f32; HALF_BAND_TAPS] = {
+    let window: [f32; HALF_BAND_LENGTH] = Window::Kaiser(7.).symmetric();
+
+    let mut taps = [0.; HALF_BAND_TAPS];
+    let mut sum = 0.;
+
+    let mut k = 0;
+    while k < HALF_BAND_TAPS {
+        let t = (2 * k) as f64 - HALF_BAND_CENTRE as f64;
+        taps[k] = math::sin(PI * t / 2.) / (PI * t) * window[2 * k] as f64;
+        sum += taps[k];
+        k += 1;
+    }
+
+    let mut result = [0.; HALF_BAND_TAPS];
+    let mut k = 0;
+    while k < HALF_BAND_TAPS {
+        result[k] = (taps[k] * 0.5 / sum) as f32;
+        k += 1;
+    }
+
+    result
+};
+
+// One 2x stage, holding the state of both the upsampling and the decimating
+// half-band filters.
+#[derive(Copy, Clone)]
+struct HalfBand {
+    // Most recent first
+    up_history: [f32; HALF_BAND_TAPS],
+    down_even: [f32; HALF_BAND_TAPS],
+    down_odd: [f32; HALF_BAND_TAPS / 2],
+}
+
+impl HalfBand {
+    const fn new() -> Self {
+        Self {
+            up_history: [0.; HALF_BAND_TAPS],
+            down_even: [0.; HALF_BAND_TAPS],
+            down_odd: [0.; HALF_BAND_TAPS / 2],
+        }
+    }
+
+    // Only even taps reach the first output, and only the centre tap the
+    // second, which is then just a delayed copy of the input.
+    #[inline]
+    fn upsample(&mut self, input: f32) -> (f32, f32) {
+        self.up_history.copy_within(..HALF_BAND_TAPS - 1, 1);
+        self.up_history[0] = input;
+
+        let mut even = 0.;
+        for (tap, sample) in HALF_BAND.iter().zip(self.up_history.iter()) {
+            even += tap * sample;
+        }
+
+        (2. * even, self.up_history[HALF_BAND_CENTRE / 2])
+    }
+
+    // The mirror image of `upsample`, where even input samples see the even
+    // taps and odd samples only the centre tap.
+    #[inline]
+    fn decimate(&mut self, even: f32, odd: f32) -> f32 {
+        self.down_even.copy_within(..HALF_BAND_TAPS - 1, 1);
+        self.down_even[0] = even;
+
+        let mut output = 0.5 * self.down_odd[HALF_BAND_TAPS / 2 - 1];
+        for (tap, sample) in HALF_BAND.iter().zip(self.down_even.iter()) {
+            output += tap * sample;
+        }
+
+        // Only needed from the next pair on
+        self.down_odd.copy_within(..HALF_BAND_TAPS / 2 - 1, 1);
+        self.down_odd[0] = odd;
+
+        output
+    }
+}
+
+/// Runs `node` at `FACTOR` times the sample rate, which must be 2, 4 or 8, to
+/// reduce aliasing from nonlinear processing. Each doubling of the rate uses a
+/// pair of linear phase half-band FIR filters.
+pub struct Oversample<N, const FACTOR: usize> {
+    node: N,
+    stages: [HalfBand; MAX_STAGES],
+}
+
+impl<N, const FACTOR: usize> Oversample<N, FACTOR>
+where
+    N: Node<f32, f32>,
+{
+    const STAGES: usize = FACTOR.trailing_zeros() as usize;
+
+    pub fn new(node: N) -> Self {
+        assert!(FACTOR == 2 || FACTOR == 4 || FACTOR == 8);
+
+        Self {
+            node,
+            stages: [HalfBand::new(); MAX_STAGES],
+        }
+    }
+
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// The delay added by the filters, in samples at the original rate. Not
+    /// a whole number of samples above 2x.
+    pub fn latency(&self) -> f32 {
+        // Each stage's pair of filters delays by the centre tap twice over,
+        // at the stage's doubled rate
+        (0..Self::STAGES)
+            .map(|stage| HALF_BAND_CENTRE as f32 / (1 << stage) as f32)
+            .sum()
+    }
+
+    pub fn reset(&mut self) {
+        self.stages = [HalfBand::new(); MAX_STAGES];
+    }
+}
+
+impl<N, const FACTOR: usize> Node<f32, f32> for Oversample<N, FACTOR>
+where
+    N: Node<f32, f32>,
+{
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let mut buffer = [0.; FACTOR];
+        let mut scratch = [0.; FACTOR];
+        buffer[0] = input;
+
+        let mut length = 1;
+        for stage in self.stages[..Self::STAGES].iter_mut() {
+            for (i, sample) in buffer[..length].iter().enumerate() {
+                let (a, b) = stage.upsample(*sample);
+                scratch[i * 2] = a;
+                scratch[i * 2 + 1] = b;
+            }
+
+            length *= 2;
+            buffer[..length].copy_from_slice(&scratch[..length]);
+        }
+
+        for sample in buffer.iter_mut() {
+            *sample = self.node.process(*sample);
+        }
+
+        for stage in self.stages[..Self::STAGES].iter_mut().rev() {
+            length /= 2;
+
+            for i in 0..length {
+                buffer[i] = stage.decimate(buffer[i * 2], buffer[i * 2 + 1]);
+            }
+        }
+
+        buffer[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::PI;
+
+    struct Identity;
+
+    impl Node<f32, f32> for Identity {
+        fn process(&mut self, input: f32) -> f32 {
+            input
+        }
+    }
+
+    struct Clip;
+
+    impl Node<f32, f32> for Clip {
+        fn process(&mut self, input: f32) -> f32 {
+            (input * 4.).clamp(-1., 1.)
+        }
+    }
+
+    fn sine(frequency: f64, n: f64) -> f32 {
+        libm::sin(2. * PI * frequency * n / 44100.) as f32
+    }
+
+    fn check_delay<const FACTOR: usize>() {
+        let mut oversample: Oversample<_, FACTOR> = Oversample::new(Identity);
+        let latency = oversample.latency() as f64;
+
+        for frequency in [100., 5000., 15000.].iter() {
+            oversample.reset();
+
+            for n in 0..2000 {
+                let output = oversample.process(sine(*frequency, n as f64));
+                let expected = sine(*frequency, n as f64 - latency);
+
+                if n > 200 {
+                    assert!((output - expected).abs() < 1e-3, "{} {}", frequency, n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_latency() {
+        check_delay::<2>();
+        check_delay::<4>();
+        check_delay::<8>();
+
+        assert_eq!(Oversample::<_, 2>::new(Identity).latency(), 23.);
+    }
+
+    // The energy of everything that isn't a harmonic of a clipped 5kHz sine,
+    // relative to the fundamental. The signal repeats every 441 samples, so
+    // everything lands on a multiple of 100Hz.
+    fn alias_db<P: Node<f32, f32>>(node: &mut P) -> f64 {
+        const LENGTH: usize = 441;
+
+        let mut output = [0.; LENGTH];
+        for n in 0..LENGTH * 4 {
+            let sample = node.process(sine(5000., n as f64));
+            if n >= LENGTH * 3 {
+                output[n - LENGTH * 3] = sample;
+            }
+        }
+
+        let energy = |frequency: f64| {
+            let (mut re, mut im) = (0., 0.);
+            for (n, sample) in output.iter().enumerate() {
+                let angle = 2. * PI * frequency * n as f64 / 44100.;
+                re += *sample as f64 * libm::cos(angle);
+                im += *sample as f64 * libm::sin(angle);
+            }
+            re * re + im * im
+        };
+
+        let aliases: f64 = (1..=220)
+            .filter(|bin| bin % 50 != 0)
+            .map(|bin| energy(bin as f64 * 100.))
+            .sum();
+
+        10. * libm::log10(aliases / energy(5000.))
+    }
+
+    #[test]
+    fn test_reduces_aliasing() {
+        let naive = alias_db(&mut Clip);
+        let twice = alias_db(&mut Oversample::<_, 2>::new(Clip));
+        let eight = alias_db(&mut Oversample::<_, 8>::new(Clip));
+
+        assert!(twice < naive - 10., "{} {}", naive, twice);
+        assert!(eight < twice - 10., "{} {}", twice, eight);
+    }
+}
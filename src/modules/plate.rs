@@ -0,0 +1,430 @@
+use super::{AllPass, DelayLine, Node, ReadableNode};
+use core::f32::consts::PI;
+use libm;
+
+// Based on Jon Dattorro's "Effect Design Part 1: Reverberator and Other
+// Filters" (1997). All lengths are in samples at the paper's 29761Hz.
+
+const PLATE_SAMPLE_RATE: usize = 29761;
+
+const INPUT_DIFFUSER_LENGTHS: [usize; 4] = [142, 107, 379, 277];
+
+// (modulated allpass, delay, allpass, delay) for each half of the tank
+const TANK_LEFT_LENGTHS: [usize; 4] = [672, 4453, 1800, 3720];
+const TANK_RIGHT_LENGTHS: [usize; 4] = [908, 4217, 2656, 3163];
+
+const MODULATION_EXCURSION: usize = 16;
+
+const OUTPUT_GAIN: f32 = 0.6;
+
+/// Scale one of Dattorro's lengths to `sample_rate`, rounding to the nearest
+/// sample.
+pub const fn plate_length(samples: usize, sample_rate: usize) -> usize {
+    (samples * sample_rate + PLATE_SAMPLE_RATE / 2) / PLATE_SAMPLE_RATE
+}
+
+// Output taps for each channel, in Dattorro's order: two taps on the other
+// half's first delay, then its allpass and second delay, then this half's
+// first delay, allpass and second delay.
+const LEFT_TAPS: [usize; 7] = [266, 2974, 1913, 1996, 1990, 187, 1066];
+const RIGHT_TAPS: [usize; 7] = [353, 3627, 1228, 2673, 2111, 335, 121];
+
+struct TankHalf<const ALLPASS: usize, const DELAY: usize> {
+    modulated: DelayLine<ALLPASS>,
+    modulated_length: usize,
+    delay_1: DelayLine<DELAY>,
+    damping_state: f32,
+    allpass: AllPass<ALLPASS>,
+    delay_2: DelayLine<DELAY>,
+}
+
+impl<const ALLPASS: usize, const DELAY: usize> TankHalf<ALLPASS, DELAY> {
+    fn new(lengths: [usize; 4], sample_rate: usize) -> Self {
+        let [modulated, delay_1, allpass, delay_2] =
+            lengths.map(|length| plate_length(length, sample_rate));
+
+        assert!(
+            modulated < ALLPASS && allpass <= ALLPASS,
+            "tank allpasses are too long for ALLPASS at this sample rate"
+        );
+        assert!(
+            delay_1 <= DELAY && delay_2 <= DELAY,
+            "tank delays are too long for DELAY at this sample rate"
+        );
+
+        Self {
+            modulated: DelayLine::new(),
+            modulated_length: modulated,
+            delay_1: DelayLine::with_length(delay_1),
+            damping_state: 0.,
+            allpass: AllPass::with_length(allpass),
+            delay_2: DelayLine::with_length(delay_2),
+        }
+    }
+
+    // Check `taps`, in the order of `LEFT_TAPS`, against the lines they read,
+    // where `self` is the half the taps are for.
+    fn check_taps(&self, other: &Self, taps: &[usize; 7]) {
+        let lengths = [
+            other.delay_1.length(),
+            other.delay_1.length(),
+            other.allpass.length(),
+            other.delay_2.length(),
+            self.delay_1.length(),
+            self.allpass.length(),
+            self.delay_2.length(),
+        ];
+
+        for (tap, length) in taps.iter().zip(lengths.iter()) {
+            assert!(tap < length, "output tap is outside its delay line");
+        }
+    }
+
+    fn end(&self) -> f32 {
+        self.delay_2.read()
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32, modulation: f32, plate: &PlateSettings) {
+        // Dattorro's tank allpass has its signs flipped, which is the same as
+        // a negative gain.
+        let gain = -plate.decay_diffusion.0;
+        let delay = (self.modulated_length - 1) as f32 + modulation;
+        let delayed = self.modulated.tap_linear(delay);
+        let buffered = input + delayed * gain;
+        self.modulated.process(buffered);
+        let diffused = delayed - buffered * gain;
+
+        let delayed = self.delay_1.read();
+        self.delay_1.process(diffused);
+
+        self.damping_state = delayed * (1. - plate.damping) + self.damping_state * plate.damping;
+
+        let diffused = self.allpass.process(self.damping_state * plate.decay);
+
+        self.delay_2.process(diffused);
+    }
+}
+
+#[derive(Copy, Clone)]
+struct PlateSettings {
+    decay: f32,
+    damping: f32,
+    decay_diffusion: (f32, f32),
+}
+
+/// Dattorro's plate reverb: a bandwidth filter and four input diffusers feed a
+/// figure-of-eight tank of modulated allpasses and damped delays, which is
+/// tapped in several places to build each output channel.
+///
+/// `PRE_DELAY` is the maximum pre-delay in samples plus one, `INPUT` the
+/// longest input diffuser, `ALLPASS` the longest tank allpass (including
+/// modulation) and `DELAY` the longest tank delay, all at the sample rate the
+/// plate runs at. `plate_length` can compute these.
+pub struct Plate<
+    const PRE_DELAY: usize,
+    const INPUT: usize,
+    const ALLPASS: usize,
+    const DELAY: usize,
+> {
+    pre_delay: DelayLine<PRE_DELAY>,
+    bandwidth_state: f32,
+    diffusers: [AllPass<INPUT>; 4],
+    left: TankHalf<ALLPASS, DELAY>,
+    right: TankHalf<ALLPASS, DELAY>,
+    // Output taps scaled to the sample rate
+    left_taps: [usize; 7],
+    right_taps: [usize; 7],
+
+    settings: PlateSettings,
+    sample_rate: f32,
+    bandwidth: f32,
+    input_diffusion: (f32, f32),
+    modulation_depth: f32,
+    modulation_rate: f32,
+    phase: f32,
+    wet: f32,
+    dry: f32,
+}
+
+impl<const PRE_DELAY: usize, const INPUT: usize, const ALLPASS: usize, const DELAY: usize>
+    Plate<PRE_DELAY, INPUT, ALLPASS, DELAY>
+{
+    pub fn new(sample_rate: f32) -> Self {
+        let rate = libm::roundf(sample_rate) as usize;
+
+        let diffuser_lengths = INPUT_DIFFUSER_LENGTHS.map(|length| plate_length(length, rate));
+        for &length in diffuser_lengths.iter() {
+            assert!(
+                length <= INPUT,
+                "input diffusers are too long for INPUT at this sample rate"
+            );
+        }
+
+        let mut plate = Self {
+            pre_delay: DelayLine::with_length(1),
+            bandwidth_state: 0.,
+            diffusers: diffuser_lengths.map(AllPass::with_length),
+            left: TankHalf::new(TANK_LEFT_LENGTHS, rate),
+            right: TankHalf::new(TANK_RIGHT_LENGTHS, rate),
+            left_taps: LEFT_TAPS.map(|tap| plate_length(tap, rate)),
+            right_taps: RIGHT_TAPS.map(|tap| plate_length(tap, rate)),
+
+            settings: PlateSettings {
+                decay: 0.,
+                damping: 0.,
+                decay_diffusion: (0., 0.),
+            },
+            sample_rate,
+            bandwidth: 0.,
+            input_diffusion: (0., 0.),
+            modulation_depth: 0.,
+            modulation_rate: 0.,
+            phase: 0.,
+            wet: 1.,
+            dry: 0.,
+        };
+
+        plate.left.check_taps(&plate.right, &plate.left_taps);
+        plate.right.check_taps(&plate.left, &plate.right_taps);
+
+        plate.set_decay(0.5);
+        plate.set_damping(0.0005);
+        plate.set_bandwidth(0.9995);
+        plate.set_input_diffusion(0.75, 0.625);
+        plate.set_decay_diffusion(0.7, 0.5);
+        plate.set_modulation(
+            MODULATION_EXCURSION as f32 * sample_rate / PLATE_SAMPLE_RATE as f32,
+            1.,
+        );
+
+        plate
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.settings.decay
+    }
+
+    /// The gain applied on each pass around the tank, below 1.
+    pub fn set_decay(&mut self, value: f32) {
+        self.settings.decay = value;
+    }
+
+    pub fn damping(&self) -> f32 {
+        self.settings.damping
+    }
+
+    /// How much high frequencies are damped in the tank, from 0 to 1.
+    pub fn set_damping(&mut self, value: f32) {
+        self.settings.damping = value;
+    }
+
+    pub fn bandwidth(&self) -> f32 {
+        self.bandwidth
+    }
+
+    /// How much of the input's high frequencies reach the tank, from 0 to 1.
+    pub fn set_bandwidth(&mut self, value: f32) {
+        self.bandwidth = value;
+    }
+
+    pub fn input_diffusion(&self) -> (f32, f32) {
+        self.input_diffusion
+    }
+
+    /// Gains for the first and second pair of input diffusers.
+    pub fn set_input_diffusion(&mut self, first: f32, second: f32) {
+        self.input_diffusion = (first, second);
+
+        self.diffusers[0].set_gain(first);
+        self.diffusers[1].set_gain(first);
+        self.diffusers[2].set_gain(second);
+        self.diffusers[3].set_gain(second);
+    }
+
+    pub fn decay_diffusion(&self) -> (f32, f32) {
+        self.settings.decay_diffusion
+    }
+
+    /// Gains for the modulated and the fixed allpass in each half of the tank.
+    pub fn set_decay_diffusion(&mut self, modulated: f32, fixed: f32) {
+        self.settings.decay_diffusion = (modulated, fixed);
+
+        self.left.allpass.set_gain(fixed);
+        self.right.allpass.set_gain(fixed);
+    }
+
+    /// Set the pre-delay in samples, up to `PRE_DELAY - 1`.
+    pub fn set_pre_delay(&mut self, samples: usize) {
+        assert!(samples < PRE_DELAY);
+        self.pre_delay.set_length(samples + 1);
+    }
+
+    pub fn modulation(&self) -> (f32, f32) {
+        (self.modulation_depth, self.modulation_rate)
+    }
+
+    /// Sweep the tank's modulated allpasses by up to `depth` samples at `rate`
+    /// Hz, with the two halves in quadrature.
+    pub fn set_modulation(&mut self, depth: f32, rate: f32) {
+        for &length in [self.left.modulated_length, self.right.modulated_length].iter() {
+            assert!(depth >= 0. && depth < length as f32 - 1.);
+            assert!(length as f32 + depth < ALLPASS as f32 - 1.);
+        }
+
+        self.modulation_depth = depth;
+        self.modulation_rate = rate;
+    }
+
+    pub fn wet(&self) -> f32 {
+        self.wet
+    }
+
+    pub fn set_wet(&mut self, value: f32) {
+        self.wet = value;
+    }
+
+    pub fn dry(&self) -> f32 {
+        self.dry
+    }
+
+    pub fn set_dry(&mut self, value: f32) {
+        self.dry = value;
+    }
+}
+
+impl<const PRE_DELAY: usize, const INPUT: usize, const ALLPASS: usize, const DELAY: usize>
+    Node<(f32, f32), (f32, f32)> for Plate<PRE_DELAY, INPUT, ALLPASS, DELAY>
+{
+    #[inline]
+    fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        let mono = self.pre_delay.process((input.0 + input.1) * 0.5);
+
+        self.bandwidth_state = mono * self.bandwidth + self.bandwidth_state * (1. - self.bandwidth);
+
+        let mut diffused = self.bandwidth_state;
+        for diffuser in self.diffusers.iter_mut() {
+            diffused = diffuser.process(diffused);
+        }
+
+        let angle = 2. * PI * self.phase;
+        let modulation = (
+            self.modulation_depth * libm::sinf(angle),
+            self.modulation_depth * libm::cosf(angle),
+        );
+
+        self.phase += self.modulation_rate / self.sample_rate;
+        if self.phase >= 1. {
+            self.phase -= 1.;
+        }
+
+        let ends = (self.left.end(), self.right.end());
+        let settings = self.settings;
+
+        self.left
+            .process(diffused + ends.1 * settings.decay, modulation.0, &settings);
+        self.right
+            .process(diffused + ends.0 * settings.decay, modulation.1, &settings);
+
+        let (l, r) = (&self.left, &self.right);
+        let (lt, rt) = (&self.left_taps, &self.right_taps);
+
+        let left = r.delay_1.tap(lt[0]) + r.delay_1.tap(lt[1]) - r.allpass.tap(lt[2])
+            + r.delay_2.tap(lt[3])
+            - l.delay_1.tap(lt[4])
+            - l.allpass.tap(lt[5])
+            - l.delay_2.tap(lt[6]);
+
+        let right = l.delay_1.tap(rt[0]) + l.delay_1.tap(rt[1]) - l.allpass.tap(rt[2])
+            + l.delay_2.tap(rt[3])
+            - r.delay_1.tap(rt[4])
+            - r.allpass.tap(rt[5])
+            - r.delay_2.tap(rt[6]);
+
+        let wet = self.wet * OUTPUT_GAIN;
+
+        (
+            left * wet + input.0 * self.dry,
+            right * wet + input.1 * self.dry,
+        )
+    }
+}
+
+pub type Plate44100 = Plate<
+    4410,
+    { plate_length(379, 44100) + 1 },
+    { plate_length(2656, 44100) + 1 },
+    { plate_length(4453, 44100) + 1 },
+>;
+
+pub type Plate48000 = Plate<
+    4800,
+    { plate_length(379, 48000) + 1 },
+    { plate_length(2656, 48000) + 1 },
+    { plate_length(4453, 48000) + 1 },
+>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tail_energy(plate: &mut Plate44100, start: usize, end: usize) -> (f32, f32) {
+        let mut energy = (0., 0.);
+
+        for i in 0..end {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            let (left, right) = plate.process(input);
+
+            assert!(left.is_finite() && right.is_finite());
+
+            if i >= start {
+                energy.0 += left * left;
+                energy.1 += right * right;
+            }
+        }
+
+        energy
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut short = Plate44100::new(44100.);
+        short.set_decay(0.3);
+
+        let mut long = Plate44100::new(44100.);
+        long.set_decay(0.9);
+
+        let short = tail_energy(&mut short, 20000, 40000);
+        let long = tail_energy(&mut long, 20000, 40000);
+
+        assert!(long.0 > short.0 * 100.);
+        assert!(long.1 > short.1 * 100.);
+    }
+
+    #[test]
+    fn test_stereo() {
+        let mut plate = Plate44100::new(44100.);
+
+        let mut difference = 0.;
+        for i in 0..10000 {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            let (left, right) = plate.process(input);
+            difference += (left - right).abs();
+        }
+
+        assert!(difference > 0.);
+    }
+
+    #[test]
+    fn test_pre_delay() {
+        let mut plate = Plate44100::new(44100.);
+        plate.set_pre_delay(1000);
+
+        // Nothing can come out before the pre-delay, diffusers and the
+        // shortest tap have all passed.
+        for i in 0..1000 {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            assert_eq!(plate.process(input), (0., 0.));
+        }
+    }
+}
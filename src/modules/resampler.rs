@@ -0,0 +1,360 @@
+use crate::math;
+use core::f64::consts::PI;
+
+// The transition band of the anti-aliasing filter, relative to the lower of
+// the two sample rates. Stopband attenuation grows with the number of taps,
+// and when downsampling shrinks in proportion to the ratio since the taps
+// span fewer output samples.
+const TRANSITION: f64 = 0.1;
+
+// Fixed point denominator of the read position in variable ratio mode.
+const VARIABLE_DENOMINATOR: u64 = 1 << 32;
+
+/// A streaming polyphase sample rate converter. Each output sample is found by
+/// filtering the last `TAPS` input samples with a windowed-sinc kernel,
+/// sampled at `PHASES` evenly spaced fractional positions and linearly
+/// interpolated in between.
+///
+/// Fixed rational ratios are tracked exactly, so there's no drift however long
+/// it runs. `set_ratio` switches to a fixed point position so the ratio can
+/// follow a drifting clock.
+pub struct Resampler<const TAPS: usize, const PHASES: usize> {
+    // Branch `p` filters for a fractional position of `p / (PHASES - 1)`
+    filters: [[f32; TAPS]; PHASES],
+    history: [f32; TAPS],
+
+    // The fractional read position is `phase / denominator`, advanced by
+    // `step` every output sample
+    phase: u64,
+    step: u64,
+    denominator: u64,
+    // Input samples still to be read before the next output
+    needed: u64,
+}
+
+/// 24 taps giving around 40dB of stopband attenuation when upsampling.
+pub type ResamplerLow = Resampler<24, 32>;
+/// 40 taps giving around 65dB of stopband attenuation when upsampling.
+pub type ResamplerMedium = Resampler<40, 64>;
+/// 64 taps giving around 100dB of stopband attenuation when upsampling.
+pub type ResamplerHigh = Resampler<64, 256>;
+
+impl<const TAPS: usize, const PHASES: usize> Resampler<TAPS, PHASES> {
+    /// Convert from `input_rate` to `output_rate`, both in Hz.
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        assert!(TAPS >= 4 && TAPS.is_multiple_of(2));
+        assert!(PHASES >= 2);
+        assert!(input_rate > 0 && output_rate > 0);
+
+        let divisor = gcd(input_rate as u64, output_rate as u64);
+        let ratio = output_rate as f64 / input_rate as f64;
+
+        let mut resampler = Self {
+            filters: [[0.; TAPS]; PHASES],
+            history: [0.; TAPS],
+
+            phase: 0,
+            step: input_rate as u64 / divisor,
+            denominator: output_rate as u64 / divisor,
+            needed: 0,
+        };
+
+        resampler.design(ratio);
+
+        resampler
+    }
+
+    // Kaiser windowed sinc, with the beta picked from the attenuation that
+    // the number of taps allows.
+    fn design(&mut self, ratio: f64) {
+        let scale = if ratio < 1. { ratio } else { 1. };
+        let transition = TRANSITION * scale;
+        let cutoff = 0.5 * scale - transition / 2.;
+
+        let attenuation = 14.36 * TAPS as f64 * transition + 7.95;
+        let beta = if attenuation > 50. {
+            0.1102 * (attenuation - 8.7)
+        } else if attenuation > 21. {
+            0.5842 * libm::pow(attenuation - 21., 0.4) + 0.07886 * (attenuation - 21.)
+        } else {
+            0.
+        };
+
+        let half = (TAPS / 2) as f64;
+        let normalisation = math::bessel_i0(beta);
+
+        for (p, filter) in self.filters.iter_mut().enumerate() {
+            let fraction = p as f64 / (PHASES - 1) as f64;
+
+            let mut taps = [0.; TAPS];
+            let mut sum = 0.;
+            for (n, tap) in taps.iter_mut().enumerate() {
+                // Ranges over the kernel's whole support as the fraction
+                // goes from 0 to 1, so branch `PHASES - 1` matches branch 0
+                // once the next sample has been read
+                let u = half - 1. - n as f64 + fraction;
+                let r = u / half;
+                if r.abs() >= 1. {
+                    continue;
+                }
+
+                let sinc = if u == 0. {
+                    2. * cutoff
+                } else {
+                    libm::sin(2. * PI * cutoff * u) / (PI * u)
+                };
+
+                *tap = sinc * math::bessel_i0(beta * libm::sqrt(1. - r * r)) / normalisation;
+                sum += *tap;
+            }
+
+            // Unity gain at DC for every fractional position
+            for (value, tap) in filter.iter_mut().zip(taps.iter()) {
+                *value = (*tap / sum) as f32;
+            }
+        }
+    }
+
+    /// Follow a drifting clock by changing the ratio of output to input rate.
+    /// The anti-aliasing filter is left as designed for the original ratio,
+    /// so this should only be used for small adjustments.
+    pub fn set_ratio(&mut self, ratio: f64) {
+        assert!(ratio > 0.);
+
+        self.phase = self.phase * VARIABLE_DENOMINATOR / self.denominator;
+        self.step = libm::round(VARIABLE_DENOMINATOR as f64 / ratio) as u64;
+        self.denominator = VARIABLE_DENOMINATOR;
+    }
+
+    /// The current ratio of output to input rate.
+    pub fn ratio(&self) -> f64 {
+        self.denominator as f64 / self.step as f64
+    }
+
+    /// The delay introduced by the filter, in input samples.
+    pub fn latency(&self) -> f32 {
+        (TAPS / 2) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.; TAPS];
+        self.phase = 0;
+        self.needed = 0;
+    }
+
+    /// Read from `input` and write to `output` until one of them runs out,
+    /// returning how many samples were read and written.
+    pub fn resample(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let mut read = 0;
+        let mut written = 0;
+
+        loop {
+            if self.needed > 0 {
+                if read == input.len() {
+                    break;
+                }
+
+                self.history.copy_within(1.., 0);
+                self.history[TAPS - 1] = input[read];
+                read += 1;
+                self.needed -= 1;
+                continue;
+            }
+
+            if written == output.len() {
+                break;
+            }
+
+            output[written] = self.interpolate();
+            written += 1;
+
+            self.phase += self.step;
+            self.needed = self.phase / self.denominator;
+            self.phase %= self.denominator;
+        }
+
+        (read, written)
+    }
+
+    fn interpolate(&self) -> f32 {
+        let position = self.phase as f64 / self.denominator as f64 * (PHASES - 1) as f64;
+        let branch = (position as usize).min(PHASES - 2);
+        let t = (position - branch as f64) as f32;
+
+        let mut a = 0.;
+        let mut b = 0.;
+        for ((sample, x), y) in self
+            .history
+            .iter()
+            .zip(self.filters[branch].iter())
+            .zip(self.filters[branch + 1].iter())
+        {
+            a += sample * x;
+            b += sample * y;
+        }
+
+        a + (b - a) * t
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTH: usize = 8000;
+
+    // Signals are generated and fitted in f64, as f32 loses too much phase
+    // precision over a few thousand samples for the noise floors measured
+    fn sine(frequency: f64, rate: f64, input: &mut [f32]) {
+        for (n, sample) in input.iter_mut().enumerate() {
+            *sample = (0.5 * libm::sin(2. * PI * frequency * n as f64 / rate)) as f32;
+        }
+    }
+
+    // Least squares fit of a sine at `frequency` to the second half of
+    // `output`, returning its amplitude and the RMS of what's left over
+    fn fit(frequency: f64, rate: f64, output: &[f32]) -> (f64, f64) {
+        let steady = &output[output.len() / 2..];
+        let w = 2. * PI * frequency / rate;
+
+        let (mut ss, mut sc, mut cc, mut ys, mut yc) = (0., 0., 0., 0., 0.);
+        for (n, &y) in steady.iter().enumerate() {
+            let (s, c) = (libm::sin(w * n as f64), libm::cos(w * n as f64));
+            ss += s * s;
+            sc += s * c;
+            cc += c * c;
+            ys += y as f64 * s;
+            yc += y as f64 * c;
+        }
+
+        let determinant = ss * cc - sc * sc;
+        let a = (ys * cc - yc * sc) / determinant;
+        let b = (yc * ss - ys * sc) / determinant;
+
+        let mut residual = 0.;
+        for (n, &y) in steady.iter().enumerate() {
+            let fitted = a * libm::sin(w * n as f64) + b * libm::cos(w * n as f64);
+            residual += (y as f64 - fitted) * (y as f64 - fitted);
+        }
+
+        (
+            libm::hypot(a, b),
+            libm::sqrt(residual / steady.len() as f64),
+        )
+    }
+
+    fn convert<const TAPS: usize, const PHASES: usize>(
+        resampler: &mut Resampler<TAPS, PHASES>,
+        input: &[f32],
+        output: &mut [f32],
+    ) {
+        let (read, written) = resampler.resample(input, output);
+        assert!(read == input.len() || written == output.len());
+    }
+
+    fn thd_n_db(input_rate: u32, output_rate: u32, frequency: f64) -> f64 {
+        let mut input = [0.; LENGTH];
+        sine(frequency, input_rate as f64, &mut input);
+
+        let mut output = [0.; LENGTH];
+        let mut resampler = ResamplerHigh::new(input_rate, output_rate);
+        convert(&mut resampler, &input, &mut output);
+
+        let (amplitude, noise) = fit(frequency, output_rate as f64, &output[..LENGTH * 8 / 10]);
+        20. * libm::log10(noise / (amplitude / core::f64::consts::SQRT_2))
+    }
+
+    #[test]
+    fn test_thd_n() {
+        for &(input_rate, output_rate) in [(44100, 48000), (48000, 44100), (44100, 96000)].iter() {
+            let thd_n = thd_n_db(input_rate, output_rate, 1000.);
+            assert!(thd_n < -100., "{} {} {}", input_rate, output_rate, thd_n);
+        }
+    }
+
+    #[test]
+    fn test_passband_ripple() {
+        for &(input_rate, output_rate) in [(44100, 48000), (48000, 44100)].iter() {
+            let passband = 0.4 * input_rate.min(output_rate) as f64;
+
+            for i in 0..10 {
+                let frequency = 20. + passband * i as f64 / 9.;
+
+                let mut input = [0.; LENGTH];
+                sine(frequency, input_rate as f64, &mut input);
+
+                let mut output = [0.; LENGTH];
+                let mut resampler = ResamplerMedium::new(input_rate, output_rate);
+                convert(&mut resampler, &input, &mut output);
+
+                let (amplitude, _) = fit(frequency, output_rate as f64, &output[..LENGTH * 8 / 10]);
+                let ripple = 20. * libm::log10(amplitude / 0.5);
+                assert!(ripple.abs() < 0.02, "{} {}", frequency, ripple);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stopband() {
+        // 23kHz at 48kHz would alias to 21.1kHz at 44.1kHz
+        let mut input = [0.; LENGTH];
+        sine(23000., 48000., &mut input);
+
+        let mut output = [0.; LENGTH];
+        let mut resampler = ResamplerHigh::new(48000, 44100);
+        convert(&mut resampler, &input, &mut output);
+
+        let steady = &output[LENGTH / 4..LENGTH / 2];
+        let peak = steady.iter().fold(0f32, |peak, y| peak.max(y.abs()));
+        assert!(20. * libm::log10f(peak / 0.5) < -85.);
+    }
+
+    #[test]
+    fn test_streaming_and_ratio() {
+        let mut input = [0.; LENGTH];
+        sine(440., 44100., &mut input);
+
+        let mut whole = [0.; LENGTH];
+        let mut resampler = ResamplerLow::new(44100, 48000);
+        let (read, written) = resampler.resample(&input[..4410], &mut whole);
+        // The first output is written before any input is read
+        assert_eq!(read, 4410);
+        assert_eq!(written, 4802);
+
+        // Feeding odd sized blocks gives identical output
+        let mut pieces = [0.; LENGTH];
+        let mut resampler = ResamplerLow::new(44100, 48000);
+        let (mut read, mut written) = (0, 0);
+        while read < 4410 {
+            let end = (read + 37).min(4410);
+            let (r, w) = resampler.resample(&input[read..end], &mut pieces[written..written + 50]);
+            read += r;
+            written += w;
+        }
+        assert_eq!(whole[..written], pieces[..written]);
+
+        // Switching to a variable ratio keeps going from the same position
+        let mut variable = [0.; LENGTH];
+        let mut resampler = ResamplerLow::new(44100, 48000);
+        resampler.set_ratio(48000. / 44100.);
+        resampler.resample(&input[..4410], &mut variable);
+        for (a, b) in whole[..4800].iter().zip(variable.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        resampler.set_ratio(1.001);
+        assert!((resampler.ratio() - 1.001).abs() < 1e-9);
+        let (read, written) = resampler.resample(&input[..4000], &mut variable);
+        assert_eq!(read, 4000);
+        assert!((written as f64 - 4004.).abs() <= 1.);
+    }
+}
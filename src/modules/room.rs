@@ -0,0 +1,167 @@
+use super::{DelayLine, EarlyReflections, EarlyTap, Freeverb44100, Node, MOORER_TAPS_44100};
+
+/// Puts a pre-delay and a tapped early reflections stage in front of a late
+/// reverb such as `Freeverb`. The input is pre-delayed, turned into early
+/// reflections, and those reflections feed the late reverb. The late reverb's
+/// own dry signal should be left at zero and `set_dry` used instead.
+pub struct Room<R, const PRE_DELAY: usize, const EARLY: usize, const TAPS: usize> {
+    pre_delay: (DelayLine<PRE_DELAY>, DelayLine<PRE_DELAY>),
+    early: EarlyReflections<EARLY, TAPS>,
+    late: R,
+    early_level: f32,
+    late_level: f32,
+    dry: f32,
+}
+
+impl<R, const PRE_DELAY: usize, const EARLY: usize, const TAPS: usize>
+    Room<R, PRE_DELAY, EARLY, TAPS>
+{
+    pub fn new(late: R, taps: [EarlyTap; TAPS]) -> Self {
+        Self {
+            pre_delay: (DelayLine::with_length(1), DelayLine::with_length(1)),
+            early: EarlyReflections::new(taps),
+            late,
+            early_level: 1.,
+            late_level: 1.,
+            dry: 0.,
+        }
+    }
+
+    pub fn late(&self) -> &R {
+        &self.late
+    }
+
+    pub fn late_mut(&mut self) -> &mut R {
+        &mut self.late
+    }
+
+    pub fn early(&self) -> &EarlyReflections<EARLY, TAPS> {
+        &self.early
+    }
+
+    pub fn early_mut(&mut self) -> &mut EarlyReflections<EARLY, TAPS> {
+        &mut self.early
+    }
+
+    /// Set the pre-delay in samples, up to `PRE_DELAY - 1`.
+    pub fn set_pre_delay(&mut self, samples: usize) {
+        assert!(samples < PRE_DELAY);
+
+        self.pre_delay.0.set_length(samples + 1);
+        self.pre_delay.1.set_length(samples + 1);
+    }
+
+    pub fn early_level(&self) -> f32 {
+        self.early_level
+    }
+
+    pub fn set_early_level(&mut self, value: f32) {
+        self.early_level = value;
+    }
+
+    pub fn late_level(&self) -> f32 {
+        self.late_level
+    }
+
+    pub fn set_late_level(&mut self, value: f32) {
+        self.late_level = value;
+    }
+
+    pub fn dry(&self) -> f32 {
+        self.dry
+    }
+
+    pub fn set_dry(&mut self, value: f32) {
+        self.dry = value;
+    }
+}
+
+impl<R, const PRE_DELAY: usize, const EARLY: usize, const TAPS: usize> Node<(f32, f32), (f32, f32)>
+    for Room<R, PRE_DELAY, EARLY, TAPS>
+where
+    R: Node<(f32, f32), (f32, f32)>,
+{
+    #[inline]
+    fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        let delayed = (
+            self.pre_delay.0.process(input.0),
+            self.pre_delay.1.process(input.1),
+        );
+
+        let early = self.early.process((delayed.0 + delayed.1) * 0.5);
+        let late = self.late.process(early);
+
+        (
+            input.0 * self.dry + early.0 * self.early_level + late.0 * self.late_level,
+            input.1 * self.dry + early.1 * self.early_level + late.1 * self.late_level,
+        )
+    }
+}
+
+/// A `Freeverb44100` with up to 100ms of pre-delay and Moorer's early
+/// reflections.
+pub type Room44100 = Room<Freeverb44100, 4410, 3516, 18>;
+
+impl Default for Room44100 {
+    fn default() -> Self {
+        Self::new(Freeverb44100::new(), MOORER_TAPS_44100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Silence;
+
+    impl Node<(f32, f32), (f32, f32)> for Silence {
+        fn process(&mut self, _: (f32, f32)) -> (f32, f32) {
+            (0., 0.)
+        }
+    }
+
+    #[test]
+    fn test_pre_delay() {
+        let tap = EarlyTap {
+            delay: 10,
+            left: 1.,
+            right: 0.5,
+        };
+
+        let mut room: Room<Silence, 8, 16, 1> = Room::new(Silence, [tap]);
+        room.set_pre_delay(5);
+
+        for i in 0..20 {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            let expected = if i == 15 { (1., 0.5) } else { (0., 0.) };
+            assert_eq!(room.process(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let mut room = Room44100::default();
+        room.set_pre_delay(441);
+        room.set_early_level(0.);
+
+        // The late reverb only hears the early reflections, so nothing comes
+        // out before the pre-delay, first tap and shortest comb have passed.
+        let silent = 441 + MOORER_TAPS_44100[0].delay + 1116;
+        for i in 0..silent {
+            let input = if i == 0 { (1., 1.) } else { (0., 0.) };
+            assert_eq!(room.process(input), (0., 0.));
+        }
+
+        let mut energy = 0.;
+        for _ in 0..5000 {
+            let (left, right) = room.process((0., 0.));
+            energy += left * left + right * right;
+        }
+        assert!(energy > 0.);
+
+        room.set_late_level(0.);
+        for _ in 0..100 {
+            assert_eq!(room.process((0., 0.)), (0., 0.));
+        }
+    }
+}
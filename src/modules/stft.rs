@@ -0,0 +1,208 @@
+use super::{Complex, Fft, Node, Window};
+
+/// Modifies the spectrum of each frame of an `Stft`. Only the first
+/// `FFT / 2 + 1` bins are used for resynthesis, the rest mirror them.
+pub trait SpectralProcessor<const FFT: usize> {
+    fn process_spectrum(&mut self, spectrum: &mut [Complex; FFT]);
+}
+
+impl<F, const FFT: usize> SpectralProcessor<FFT> for F
+where
+    F: FnMut(&mut [Complex; FFT]),
+{
+    fn process_spectrum(&mut self, spectrum: &mut [Complex; FFT]) {
+        self(spectrum)
+    }
+}
+
+/// A streaming short-time Fourier transform. Every `hop` samples the last
+/// `FFT` samples are windowed and transformed, handed to a
+/// `SpectralProcessor`, then transformed back, windowed again and overlap
+/// added into the output.
+///
+/// The output is normalised by the overlapping squared windows, so an
+/// identity processor reconstructs the input exactly, delayed by `latency`.
+pub struct Stft<P, const FFT: usize> {
+    processor: P,
+    fft: Fft<FFT>,
+    window: [f32; FFT],
+    // Inverse of the summed squared windows, only the first `hop` are used
+    normalisation: [f32; FFT],
+    hop: usize,
+
+    input: [f32; FFT],
+    output: [f32; FFT],
+    spectrum: [Complex; FFT],
+    position: usize,
+}
+
+impl<P, const FFT: usize> Stft<P, FFT>
+where
+    P: SpectralProcessor<FFT>,
+{
+    /// An STFT using a periodic Hann window.
+    pub fn new(processor: P, hop: usize) -> Self {
+        Self::with_window(processor, hop, Window::Hann.periodic())
+    }
+
+    /// `hop` must divide `FFT`, and the squared window must not sum to zero
+    /// anywhere when overlapped at that hop.
+    pub fn with_window(processor: P, hop: usize, window: [f32; FFT]) -> Self {
+        assert!(FFT >= 2);
+        assert!(hop > 0 && FFT.is_multiple_of(hop));
+
+        let mut normalisation = [0.; FFT];
+        for (i, value) in normalisation[..hop].iter_mut().enumerate() {
+            let sum: f32 = window[i..].iter().step_by(hop).map(|w| w * w).sum();
+            assert!(sum > 0.);
+
+            *value = 1. / sum;
+        }
+
+        Self {
+            processor,
+            fft: Fft::new(),
+            window,
+            normalisation,
+            hop,
+
+            input: [0.; FFT],
+            output: [0.; FFT],
+            spectrum: [Complex::default(); FFT],
+            position: 0,
+        }
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// The number of samples the output is delayed by.
+    pub fn latency(&self) -> usize {
+        FFT
+    }
+
+    pub fn reset(&mut self) {
+        self.input = [0.; FFT];
+        self.output = [0.; FFT];
+        self.position = 0;
+    }
+
+    fn process_frame(&mut self) {
+        let hop = self.hop;
+
+        let mut frame = [0.; FFT];
+        for ((value, sample), w) in frame
+            .iter_mut()
+            .zip(self.input.iter())
+            .zip(self.window.iter())
+        {
+            *value = sample * w;
+        }
+
+        self.fft.forward_real(&frame, &mut self.spectrum);
+        self.processor.process_spectrum(&mut self.spectrum);
+        self.fft.inverse_real(&self.spectrum, &mut frame);
+
+        self.output.copy_within(hop.., 0);
+        self.output[FFT - hop..]
+            .iter_mut()
+            .for_each(|value| *value = 0.);
+
+        for (i, (value, sample)) in self.output.iter_mut().zip(frame.iter()).enumerate() {
+            *value += sample * self.window[i] * self.normalisation[i % hop];
+        }
+
+        self.input.copy_within(hop.., 0);
+    }
+}
+
+impl<P, const FFT: usize> Node<f32, f32> for Stft<P, FFT>
+where
+    P: SpectralProcessor<FFT>,
+{
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        self.input[FFT - self.hop + self.position] = input;
+        let output = self.output[self.position];
+
+        self.position += 1;
+        if self.position == self.hop {
+            self.position = 0;
+            self.process_frame();
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(seed: &mut u32) -> f32 {
+        *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (*seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+    }
+
+    fn check_reconstruction<P: SpectralProcessor<64>>(stft: &mut Stft<P, 64>, gain: f32) {
+        let latency = stft.latency();
+        let mut seed = 3;
+
+        let mut input = [0.; 1000];
+        for sample in input.iter_mut() {
+            *sample = noise(&mut seed);
+        }
+
+        for (n, sample) in input.iter().enumerate() {
+            let output = stft.process(*sample);
+            let expected = if n >= latency {
+                input[n - latency] * gain
+            } else {
+                0.
+            };
+
+            assert!(
+                (output - expected).abs() < 1e-5,
+                "{} {} {}",
+                n,
+                output,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_identity_reconstructs() {
+        for &hop in [8, 16, 32].iter() {
+            let mut stft: Stft<_, 64> = Stft::new(|_: &mut [Complex; 64]| {}, hop);
+            check_reconstruction(&mut stft, 1.);
+        }
+    }
+
+    #[test]
+    fn test_rectangular_window() {
+        let mut stft: Stft<_, 64> = Stft::with_window(|_: &mut [Complex; 64]| {}, 64, [1.; 64]);
+        check_reconstruction(&mut stft, 1.);
+    }
+
+    #[test]
+    fn test_spectral_gain() {
+        let halve = |spectrum: &mut [Complex; 64]| {
+            for bin in spectrum.iter_mut() {
+                *bin = *bin * 0.5;
+            }
+        };
+
+        let mut stft: Stft<_, 64> = Stft::new(halve, 16);
+        check_reconstruction(&mut stft, 0.5);
+    }
+}
@@ -0,0 +1,365 @@
+use super::biquad::Biquad;
+use super::Node;
+use libm;
+
+// Below this difference between samples the antiderivative quotient is
+// ill-conditioned, so the curve is evaluated at the midpoint instead.
+const ADAA_EPSILON: f64 = 1e-6;
+
+#[derive(Copy, Clone)]
+pub enum ShaperCurve {
+    Tanh,
+    /// Clamps to [-1, 1].
+    HardClip,
+    /// A cubic that reaches ±1 with zero slope at ±1.
+    SoftClip,
+    /// Reflects the signal back off ±1 as many times as needed, giving a
+    /// triangle shaped transfer curve.
+    Foldback,
+    /// A tanh with its input offset by `bias`, so positive and negative halves
+    /// saturate differently and even harmonics are added. The curve passes
+    /// through zero, but the output will carry a DC offset.
+    Tube(f32),
+    /// A user supplied curve along with its antiderivative.
+    Custom {
+        curve: fn(f64) -> f64,
+        antiderivative: fn(f64) -> f64,
+    },
+}
+
+impl ShaperCurve {
+    pub fn shape(&self, x: f64) -> f64 {
+        match *self {
+            ShaperCurve::Tanh => libm::tanh(x),
+            ShaperCurve::HardClip => x.clamp(-1., 1.),
+            ShaperCurve::SoftClip => {
+                if x.abs() <= 1. {
+                    1.5 * x - 0.5 * x * x * x
+                } else {
+                    x.signum()
+                }
+            }
+            ShaperCurve::Foldback => {
+                let t = fold_position(x);
+                if t < 2. {
+                    t - 1.
+                } else {
+                    3. - t
+                }
+            }
+            ShaperCurve::Tube(bias) => {
+                let bias = bias as f64;
+                libm::tanh(x + bias) - libm::tanh(bias)
+            }
+            ShaperCurve::Custom { curve, .. } => curve(x),
+        }
+    }
+
+    pub fn antiderivative(&self, x: f64) -> f64 {
+        match *self {
+            ShaperCurve::Tanh => log_cosh(x),
+            ShaperCurve::HardClip => {
+                if x.abs() <= 1. {
+                    0.5 * x * x
+                } else {
+                    x.abs() - 0.5
+                }
+            }
+            ShaperCurve::SoftClip => {
+                if x.abs() <= 1. {
+                    let x2 = x * x;
+                    0.75 * x2 - 0.125 * x2 * x2
+                } else {
+                    x.abs() - 0.375
+                }
+            }
+            ShaperCurve::Foldback => {
+                // The triangle has zero mean, so this is periodic too
+                let t = fold_position(x);
+                if t < 2. {
+                    0.5 * (t - 1.) * (t - 1.) - 0.5
+                } else {
+                    0.5 - 0.5 * (3. - t) * (3. - t)
+                }
+            }
+            ShaperCurve::Tube(bias) => {
+                let bias = bias as f64;
+                log_cosh(x + bias) - libm::tanh(bias) * x
+            }
+            ShaperCurve::Custom { antiderivative, .. } => antiderivative(x),
+        }
+    }
+}
+
+// Where `x` falls within one period of the foldback triangle, from 0 to 4.
+fn fold_position(x: f64) -> f64 {
+    let t = x + 1.;
+    t - 4. * libm::floor(t / 4.)
+}
+
+// ln(cosh(x)), written so it doesn't overflow for large inputs.
+fn log_cosh(x: f64) -> f64 {
+    let x = x.abs();
+    x + libm::log1p(libm::exp(-2. * x)) - core::f64::consts::LN_2
+}
+
+/// A memoryless waveshaper using first order antiderivative anti-aliasing,
+/// which suppresses much of the aliasing from the curve without
+/// oversampling. This delays the shaped signal by half a sample relative to
+/// the dry signal.
+///
+/// Optional filters can be placed before the curve, to shape what gets
+/// distorted, and after it to tame the harmonics.
+pub struct Waveshaper {
+    curve: ShaperCurve,
+    drive: f32,
+    output_gain: f32,
+    mix: f32,
+    pre: Option<Biquad>,
+    post: Option<Biquad>,
+
+    previous: f64,
+    previous_antiderivative: f64,
+}
+
+impl Waveshaper {
+    pub fn new(curve: ShaperCurve) -> Self {
+        Self {
+            curve,
+            drive: 1.,
+            output_gain: 1.,
+            mix: 1.,
+            pre: None,
+            post: None,
+
+            previous: 0.,
+            previous_antiderivative: curve.antiderivative(0.),
+        }
+    }
+
+    pub fn curve(&self) -> ShaperCurve {
+        self.curve
+    }
+
+    pub fn set_curve(&mut self, curve: ShaperCurve) {
+        self.curve = curve;
+        self.previous_antiderivative = curve.antiderivative(self.previous);
+    }
+
+    pub fn drive(&self) -> f32 {
+        self.drive
+    }
+
+    /// Set the gain applied before the curve.
+    pub fn set_drive(&mut self, value: f32) {
+        self.drive = value;
+    }
+
+    pub fn output_gain(&self) -> f32 {
+        self.output_gain
+    }
+
+    pub fn set_output_gain(&mut self, value: f32) {
+        self.output_gain = value;
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the balance between the dry input at 0 and the shaped signal at
+    /// 1.
+    pub fn set_mix(&mut self, value: f32) {
+        self.mix = value;
+    }
+
+    pub fn set_pre_filter(&mut self, filter: Option<Biquad>) {
+        self.pre = filter;
+    }
+
+    pub fn set_post_filter(&mut self, filter: Option<Biquad>) {
+        self.post = filter;
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = 0.;
+        self.previous_antiderivative = self.curve.antiderivative(0.);
+    }
+
+    #[inline]
+    fn shape(&mut self, x: f64) -> f64 {
+        let antiderivative = self.curve.antiderivative(x);
+        let difference = x - self.previous;
+
+        let output = if difference.abs() < ADAA_EPSILON {
+            self.curve.shape(0.5 * (x + self.previous))
+        } else {
+            (antiderivative - self.previous_antiderivative) / difference
+        };
+
+        self.previous = x;
+        self.previous_antiderivative = antiderivative;
+
+        output
+    }
+}
+
+impl Node<f32, f32> for Waveshaper {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let mut wet = input;
+
+        if let Some(pre) = self.pre.as_mut() {
+            wet = pre.process(wet);
+        }
+
+        wet = self.shape((wet * self.drive) as f64) as f32 * self.output_gain;
+
+        if let Some(post) = self.post.as_mut() {
+            wet = post.process(wet);
+        }
+
+        input + (wet - input) * self.mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::biquad::{BiquadParameters, BiquadType};
+    use super::*;
+    use core::f64::consts::PI;
+
+    fn cube(x: f64) -> f64 {
+        x * x * x
+    }
+
+    fn quartic(x: f64) -> f64 {
+        0.25 * x * x * x * x
+    }
+
+    const CURVES: [ShaperCurve; 6] = [
+        ShaperCurve::Tanh,
+        ShaperCurve::HardClip,
+        ShaperCurve::SoftClip,
+        ShaperCurve::Foldback,
+        ShaperCurve::Tube(0.3),
+        ShaperCurve::Custom {
+            curve: cube,
+            antiderivative: quartic,
+        },
+    ];
+
+    #[test]
+    fn test_antiderivatives() {
+        let h = 1e-5;
+
+        for curve in CURVES.iter() {
+            for i in -400..400 {
+                // Avoid landing exactly on a kink
+                let x = i as f64 * 0.0137 + 0.001;
+                let slope = (curve.antiderivative(x + h) - curve.antiderivative(x - h)) / (2. * h);
+                assert!((slope - curve.shape(x)).abs() < 1e-5, "{}", x);
+            }
+
+            assert!(curve.shape(0.).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_follows_curve_for_slow_input() {
+        for curve in CURVES.iter() {
+            let mut shaper = Waveshaper::new(*curve);
+            shaper.set_drive(3.);
+
+            // Lags the curve by half a sample
+            shaper.process(0.);
+            for n in 1..1000 {
+                let input = libm::sinf(n as f32 * 0.001);
+                let expected = curve.shape(3. * (input as f64 - 0.0005)) as f32;
+                let output = shaper.process(input);
+
+                assert!(
+                    (output - expected).abs() < 1e-3 * expected.abs().max(1.),
+                    "{} {}",
+                    output,
+                    expected
+                );
+            }
+        }
+    }
+
+    // The energy of everything that isn't a harmonic of a 5kHz sine, relative
+    // to the fundamental. The signal repeats every 441 samples, so
+    // everything lands on a multiple of 100Hz.
+    fn alias_db(output: &[f32; 441]) -> f64 {
+        let energy = |frequency: f64| {
+            let (mut re, mut im) = (0., 0.);
+            for (n, sample) in output.iter().enumerate() {
+                let angle = 2. * PI * frequency * n as f64 / 44100.;
+                re += *sample as f64 * libm::cos(angle);
+                im += *sample as f64 * libm::sin(angle);
+            }
+            re * re + im * im
+        };
+
+        let aliases: f64 = (1..=220)
+            .filter(|bin| bin % 50 != 0)
+            .map(|bin| energy(bin as f64 * 100.))
+            .sum();
+
+        10. * libm::log10(aliases / energy(5000.))
+    }
+
+    #[test]
+    fn test_reduces_aliasing() {
+        for curve in [ShaperCurve::HardClip, ShaperCurve::Tanh].iter() {
+            let mut shaper = Waveshaper::new(*curve);
+            shaper.set_drive(4.);
+
+            let mut naive = [0.; 441];
+            let mut adaa = [0.; 441];
+            for n in 0..441 * 2 {
+                let input = libm::sin(2. * PI * 5000. * n as f64 / 44100.);
+                let output = shaper.process(input as f32);
+
+                if n >= 441 {
+                    naive[n - 441] = curve.shape(4. * input) as f32;
+                    adaa[n - 441] = output;
+                }
+            }
+
+            let (naive, adaa) = (alias_db(&naive), alias_db(&adaa));
+            assert!(adaa < naive - 6., "{} {}", naive, adaa);
+        }
+    }
+
+    #[test]
+    fn test_mix_and_filters() {
+        let mut shaper = Waveshaper::new(ShaperCurve::HardClip);
+        shaper.set_drive(10.);
+        shaper.set_mix(0.);
+        for n in 0..100 {
+            let input = libm::sinf(n as f32 * 0.3);
+            assert_eq!(shaper.process(input), input);
+        }
+
+        // A post filter that blocks everything
+        let parameters = BiquadParameters {
+            cutoff: 0.25,
+            q: 0.707,
+            peak_gain: -120.,
+        };
+        shaper.set_post_filter(Some(Biquad::new(BiquadType::Peak, parameters)));
+        shaper.set_mix(1.);
+        shaper.set_curve(ShaperCurve::Tanh);
+
+        let mut energy = 0.;
+        for n in 0..1000 {
+            let output = shaper.process(libm::sinf(n as f32 * PI as f32 / 2.));
+            if n > 500 {
+                energy += output * output;
+            }
+        }
+        assert!(energy < 1e-6, "{}", energy);
+    }
+}
@@ -0,0 +1,283 @@
+use super::{interpolate_linear, Complex, Fft, Node, ReadableNode};
+
+/// How many band limited levels a `Wavetable` keeps of each frame. Each level
+/// halves the harmonics of the one before, so this covers tables of up to
+/// 2048 samples down to a single harmonic.
+pub const MIP_LEVELS: usize = 11;
+
+/// A wavetable oscillator, morphing between `FRAMES` single cycle waveforms of
+/// `SIZE` samples each. The input to `process` is added to the frequency, for
+/// linear FM.
+///
+/// Each frame is stored at several levels of band limiting, one per octave,
+/// and the level is picked from the frequency so that no harmonic passes
+/// Nyquist. Frequencies are fractions of the sample rate.
+///
+/// The levels are kept in a buffer provided by the caller, `MIP_LEVELS` of
+/// `FRAMES * SIZE` samples, so that large tables don't need to live on the
+/// stack. Building them in `new` and `set_frames` still takes `24 * SIZE`
+/// bytes of stack for the FFT and its scratch spectra, 48 KB at the largest
+/// size. `SIZE` must be a power of two no larger than 2048.
+pub struct Wavetable<'a, const SIZE: usize, const FRAMES: usize> {
+    tables: &'a mut [[[f32; SIZE]; FRAMES]; MIP_LEVELS],
+    frequency: f32,
+    position: f32,
+
+    phase: f32,
+    output: f32,
+}
+
+impl<'a, const SIZE: usize, const FRAMES: usize> Wavetable<'a, SIZE, FRAMES> {
+    /// Build the levels of `frames` into `tables`, overwriting what was there.
+    pub fn new(
+        frames: &[[f32; SIZE]; FRAMES],
+        tables: &'a mut [[[f32; SIZE]; FRAMES]; MIP_LEVELS],
+        frequency: f32,
+    ) -> Self {
+        assert!(SIZE.is_power_of_two() && SIZE >= 2 && SIZE <= 1 << MIP_LEVELS);
+        assert!(FRAMES > 0);
+
+        let mut wavetable = Self {
+            tables,
+            frequency: 0.,
+            position: 0.,
+
+            phase: 0.,
+            output: 0.,
+        };
+
+        wavetable.set_frequency(frequency);
+        wavetable.set_frames(frames);
+
+        wavetable
+    }
+
+    /// Replace the waveforms, building each of their band limited levels.
+    /// Uses `24 * SIZE` bytes of stack while it runs.
+    pub fn set_frames(&mut self, frames: &[[f32; SIZE]; FRAMES]) {
+        let fft = Fft::<SIZE>::new();
+        let mut spectrum = [Complex::default(); SIZE];
+        let mut limited = [Complex::default(); SIZE];
+
+        for (frame, waveform) in frames.iter().enumerate() {
+            fft.forward_real(waveform, &mut spectrum);
+
+            for level in 0..MIP_LEVELS {
+                let harmonics = Self::harmonics(level);
+
+                for (bin, value) in limited.iter_mut().enumerate().take(SIZE / 2 + 1) {
+                    *value = if bin <= harmonics && bin < SIZE / 2 {
+                        spectrum[bin]
+                    } else {
+                        Complex::default()
+                    };
+                }
+
+                fft.inverse_real(&limited, &mut self.tables[level][frame]);
+            }
+        }
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        assert!((0. ..0.5).contains(&frequency));
+
+        self.frequency = frequency;
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Set where to read between the first frame at 0 and the last at 1,
+    /// crossfading between neighbouring frames. Cheap enough to modulate
+    /// every sample.
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0., 1.);
+    }
+
+    /// How far through the cycle the oscillator is, from 0 to 1.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    pub fn set_phase(&mut self, phase: f32) {
+        assert!((0. ..1.).contains(&phase));
+
+        self.phase = phase;
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.;
+        self.output = 0.;
+    }
+
+    // The highest harmonic kept at `level`.
+    fn harmonics(level: usize) -> usize {
+        ((SIZE / 2) >> level).max(1)
+    }
+
+    // The least band limited level whose harmonics all stay below Nyquist.
+    fn level(frequency: f32) -> usize {
+        let mut level = 0;
+        while level < MIP_LEVELS - 1 && Self::harmonics(level) as f32 * frequency >= 0.5 {
+            level += 1;
+        }
+        level
+    }
+
+    // Interpolates between samples for fractional positions in the cycle.
+    #[inline]
+    fn lookup(table: &[f32; SIZE], phase: f32) -> f32 {
+        interpolate_linear(phase * SIZE as f32, |index| table[index % SIZE])
+    }
+}
+
+impl<const SIZE: usize, const FRAMES: usize> Node<f32, f32> for Wavetable<'_, SIZE, FRAMES> {
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let frequency = (self.frequency + input).clamp(0., 0.5);
+        let frames = &self.tables[Self::level(frequency)];
+
+        let position = self.position * (FRAMES - 1) as f32;
+        let frame = (position as usize).min(FRAMES - 1);
+        let fraction = position - frame as f32;
+
+        let a = Self::lookup(&frames[frame], self.phase);
+        self.output = if fraction > 0. {
+            a + (Self::lookup(&frames[frame + 1], self.phase) - a) * fraction
+        } else {
+            a
+        };
+
+        self.phase += frequency;
+        if self.phase >= 1. {
+            self.phase -= 1.;
+        }
+
+        self.output
+    }
+}
+
+impl<const SIZE: usize, const FRAMES: usize> ReadableNode<f32> for Wavetable<'_, SIZE, FRAMES> {
+    /// The sample most recently returned by `process`.
+    #[inline]
+    fn read(&self) -> f32 {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+
+    const SIZE: usize = 256;
+
+    fn sine(harmonic: usize) -> [f32; SIZE] {
+        let mut table = [0.; SIZE];
+        for (n, sample) in table.iter_mut().enumerate() {
+            *sample = libm::sinf(2. * PI * (harmonic * n) as f32 / SIZE as f32);
+        }
+        table
+    }
+
+    fn saw() -> [f32; SIZE] {
+        let mut table = [0.; SIZE];
+        for (n, sample) in table.iter_mut().enumerate() {
+            *sample = 2. * n as f32 / SIZE as f32 - 1.;
+        }
+        table
+    }
+
+    #[test]
+    fn test_sine() {
+        let mut tables = [[[0.; SIZE]; 1]; MIP_LEVELS];
+        let mut wavetable = Wavetable::new(&[sine(1)], &mut tables, 0.01);
+
+        for n in 0..1000 {
+            let expected = libm::sinf(2. * PI * 0.01 * n as f32);
+            let output = wavetable.process(0.);
+
+            assert!((output - expected).abs() < 1e-3, "{} {}", output, expected);
+            assert_eq!(wavetable.read(), output);
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        type Table<'a> = Wavetable<'a, SIZE, 1>;
+
+        assert_eq!(Table::level(0.001), 0);
+        assert_eq!(Table::harmonics(Table::level(0.49)), 1);
+
+        for &frequency in [0.01, 0.07, 0.2].iter() {
+            let level = Table::level(frequency);
+
+            // As many harmonics as will fit below Nyquist
+            assert!(Table::harmonics(level) as f32 * frequency < 0.5);
+            assert!(Table::harmonics(level - 1) as f32 * frequency >= 0.5);
+        }
+    }
+
+    #[test]
+    fn test_band_limited() {
+        const LENGTH: usize = 4096;
+        // Lands exactly on a bin
+        const BIN: usize = 297;
+
+        let mut tables = [[[0.; SIZE]; 1]; MIP_LEVELS];
+        let mut wavetable = Wavetable::new(&[saw()], &mut tables, BIN as f32 / LENGTH as f32);
+
+        let mut signal = [0.; LENGTH];
+        for sample in signal.iter_mut() {
+            *sample = wavetable.process(0.);
+        }
+
+        let mut spectrum = [Complex::default(); LENGTH];
+        Fft::<LENGTH>::new().forward_real(&signal, &mut spectrum);
+
+        // Everything that isn't a harmonic, relative to the fundamental
+        let aliases: f32 = (1..LENGTH / 2)
+            .filter(|bin| bin % BIN != 0)
+            .map(|bin| spectrum[bin].norm_sqr())
+            .sum();
+        let db = 10. * libm::log10f(aliases / spectrum[BIN].norm_sqr());
+
+        assert!(db < -40., "{}", db);
+    }
+
+    #[test]
+    fn test_position() {
+        let frames = [sine(1), sine(2), sine(3)];
+        let mut tables = [[[0.; SIZE]; 3]; MIP_LEVELS];
+        let mut reference_tables = [[[0.; SIZE]; 3]; MIP_LEVELS];
+        let mut wavetable = Wavetable::new(&frames, &mut tables, 0.01);
+        let mut reference = Wavetable::new(&frames, &mut reference_tables, 0.01);
+
+        for &(position, first, second) in [(0., 0, 0), (1., 2, 2), (0.25, 0, 1)].iter() {
+            wavetable.reset();
+            wavetable.set_position(position);
+
+            for n in 0..100 {
+                let phase = 0.01 * n as f32;
+                let fraction = position * 2. - first as f32;
+                let a = Wavetable::<SIZE, 3>::lookup(&reference.tables[0][first], phase);
+                let b = Wavetable::<SIZE, 3>::lookup(&reference.tables[0][second], phase);
+
+                let output = wavetable.process(0.);
+                assert!((output - (a + (b - a) * fraction)).abs() < 1e-4);
+            }
+        }
+
+        reference.set_position(0.25);
+        for n in 0..100 {
+            let expected = libm::sinf(2. * PI * 0.01 * n as f32) * 0.5
+                + libm::sinf(4. * PI * 0.01 * n as f32) * 0.5;
+            assert!((reference.process(0.) - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::math;
+use core::f64::consts::PI;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    /// The four term Blackman-Harris window, with sidelobes below -92dB.
+    BlackmanHarris,
+    /// A Kaiser window with the given beta. Larger values trade a wider main
+    /// lobe for lower sidelobes.
+    Kaiser(f32),
+    /// A Tukey window where the given fraction, from 0 to 1, is tapered.
+    Tukey(f32),
+    /// A five term flat-top window, for measuring amplitudes accurately.
+    FlatTop,
+    /// A Gaussian window with the given standard deviation, relative to half
+    /// the window length.
+    Gaussian(f32),
+}
+
+impl Window {
+    /// A window of `N` samples that is symmetric about its centre, as used for
+    /// FIR filter design.
+    pub const fn symmetric<const N: usize>(self) -> [f32; N] {
+        let mut window = [0.; N];
+
+        let mut i = 0;
+        while i < N {
+            window[i] = self.value(i, N - 1) as f32;
+            i += 1;
+        }
+
+        window
+    }
+
+    /// A window of `N` samples that repeats with period `N`, as used for
+    /// spectral analysis and the STFT.
+    pub const fn periodic<const N: usize>(self) -> [f32; N] {
+        let mut window = [0.; N];
+
+        let mut i = 0;
+        while i < N {
+            window[i] = self.value(i, N) as f32;
+            i += 1;
+        }
+
+        window
+    }
+
+    pub fn fill_symmetric(self, window: &mut [f32]) {
+        let last = window.len().saturating_sub(1);
+
+        for (i, value) in window.iter_mut().enumerate() {
+            *value = self.value(i, last) as f32;
+        }
+    }
+
+    pub fn fill_periodic(self, window: &mut [f32]) {
+        let length = window.len();
+
+        for (i, value) in window.iter_mut().enumerate() {
+            *value = self.value(i, length) as f32;
+        }
+    }
+
+    // The value at sample `n` of a symmetric window spanning `span + 1`
+    // samples.
+    const fn value(self, n: usize, span: usize) -> f64 {
+        if span == 0 {
+            return 1.;
+        }
+
+        let x = n as f64 / span as f64;
+
+        match self {
+            Window::Rectangular => 1.,
+            Window::Hann => cosine_sum(&[0.5, 0.5], x),
+            Window::Hamming => cosine_sum(&[0.54, 0.46], x),
+            Window::Blackman => cosine_sum(&[0.42, 0.5, 0.08], x),
+            Window::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168], x),
+            Window::FlatTop => cosine_sum(
+                &[
+                    0.21557895,
+                    0.41663158,
+                    0.277263158,
+                    0.083578947,
+                    0.006947368,
+                ],
+                x,
+            ),
+            Window::Kaiser(beta) => {
+                let beta = beta as f64;
+                let r = 2. * x - 1.;
+                math::bessel_i0(beta * math::sqrt(1. - r * r)) / math::bessel_i0(beta)
+            }
+            Window::Tukey(alpha) => {
+                let alpha = alpha as f64;
+                let edge = if x < 0.5 { x } else { 1. - x };
+
+                if edge >= alpha / 2. {
+                    1.
+                } else {
+                    0.5 - 0.5 * math::cos(2. * PI * edge / alpha)
+                }
+            }
+            Window::Gaussian(sigma) => {
+                assert!(sigma > 0.);
+
+                let r = (2. * x - 1.) / sigma as f64;
+                math::exp(-0.5 * r * r)
+            }
+        }
+    }
+}
+
+// a0 - a1 cos(2 pi x) + a2 cos(4 pi x) - ...
+const fn cosine_sum(coefficients: &[f64], x: f64) -> f64 {
+    let mut sum = 0.;
+    let mut sign = 1.;
+
+    let mut k = 0;
+    while k < coefficients.len() {
+        sum += sign * coefficients[k] * math::cos(2. * PI * k as f64 * x);
+        sign = -sign;
+        k += 1;
+    }
+
+    sum
+}
+
+/// The average gain of a window, which a sinusoid's peak in the spectrum is
+/// scaled by.
+pub fn coherent_gain(window: &[f32]) -> f32 {
+    window.iter().sum::<f32>() / window.len() as f32
+}
+
+/// The equivalent noise bandwidth of a window in bins, which the power of
+/// broadband noise in each bin is scaled by.
+pub fn enbw(window: &[f32]) -> f32 {
+    let sum: f32 = window.iter().sum();
+    let sum_squares: f32 = window.iter().map(|w| w * w).sum();
+
+    window.len() as f32 * sum_squares / (sum * sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static HANN: [f32; 1024] = Window::Hann.periodic();
+
+    #[test]
+    fn test_gains() {
+        let windows = [
+            (Window::Rectangular, 1., 1.),
+            (Window::Hann, 0.5, 1.5),
+            (Window::Hamming, 0.54, 1.3628),
+            (Window::Blackman, 0.42, 1.7268),
+            (Window::BlackmanHarris, 0.35875, 2.0044),
+            (Window::FlatTop, 0.21557895, 3.7702),
+        ];
+
+        for &(window, gain, bandwidth) in windows.iter() {
+            let mut values = [0.; 1024];
+            window.fill_periodic(&mut values);
+
+            assert!((coherent_gain(&values) - gain).abs() < 1e-4, "{:?}", window);
+            assert!((enbw(&values) - bandwidth).abs() < 1e-3, "{:?}", window);
+        }
+
+        assert!((coherent_gain(&HANN) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_const_matches_runtime() {
+        let windows = [
+            Window::Hann,
+            Window::Kaiser(8.6),
+            Window::Tukey(0.3),
+            Window::Gaussian(0.4),
+        ];
+
+        for &window in windows.iter() {
+            let mut values = [0.; 31];
+            window.fill_symmetric(&mut values);
+            assert_eq!(values, window.symmetric::<31>());
+
+            window.fill_periodic(&mut values);
+            assert_eq!(values, window.periodic::<31>());
+        }
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let windows = [
+            Window::Hamming,
+            Window::BlackmanHarris,
+            Window::Kaiser(5.),
+            Window::Tukey(0.5),
+            Window::Gaussian(0.3),
+        ];
+
+        for &window in windows.iter() {
+            let values: [f32; 33] = window.symmetric();
+
+            assert!((values[16] - 1.).abs() < 1e-6, "{:?}", window);
+            for i in 0..33 {
+                assert!((values[i] - values[32 - i]).abs() < 1e-6, "{:?}", window);
+            }
+        }
+    }
+
+    #[test]
+    fn test_special_cases() {
+        let hann: [f32; 65] = Window::Hann.symmetric();
+        let tukey: [f32; 65] = Window::Tukey(1.).symmetric();
+        let rectangular: [f32; 65] = Window::Tukey(0.).symmetric();
+        let kaiser: [f32; 65] = Window::Kaiser(0.).symmetric();
+
+        for i in 0..65 {
+            assert!((hann[i] - tukey[i]).abs() < 1e-6);
+            assert_eq!(rectangular[i], 1.);
+            assert_eq!(kaiser[i], 1.);
+        }
+
+        // Narrow enough that everything but the centre underflows
+        let gaussian: [f32; 65] = Window::Gaussian(0.0005).symmetric();
+        assert_eq!(gaussian[32], 1.);
+        assert_eq!(gaussian[31], 0.);
+
+        assert!(hann[0].abs() < 1e-7);
+        assert_eq!(Window::Blackman.symmetric::<1>(), [1.]);
+    }
+}
@@ -5,3 +5,15 @@ pub trait Node<Input, Output> {
 pub trait ReadableNode<Output> {
     fn read(&self) -> Output;
 }
+
+/// A pair of mono nodes processes a stereo signal, one channel each.
+impl<Input, Output, Left, Right> Node<(Input, Input), (Output, Output)> for (Left, Right)
+where
+    Left: Node<Input, Output>,
+    Right: Node<Input, Output>,
+{
+    #[inline]
+    fn process(&mut self, input: (Input, Input)) -> (Output, Output) {
+        (self.0.process(input.0), self.1.process(input.1))
+    }
+}